@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+/// Appends one JSON line per request sent to an external API.
+///
+/// Only a SHA-256 hash of the text is logged unless full text logging is enabled.
+/// Records are written on a background thread, so logging never holds up a request.
+pub struct AuditLog {
+    full_text: bool,
+    sender: Option<Sender<String>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+/// Records queued in any audit log but not written yet
+static UNWRITTEN: Mutex<usize> = Mutex::new(0);
+static WRITTEN: Condvar = Condvar::new();
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    timestamp: String,
+    model: &'a str,
+    source_lang: Option<&'a str>,
+    target_lang: &'a str,
+    char_count: usize,
+    text_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<&'a str>,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path, full_text: bool) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+
+        let (sender, receiver) = mpsc::channel();
+        let writer = thread::spawn(move || write_records(file, receiver));
+
+        Ok(Self {
+            full_text,
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn record(&self, model: &str, text: &str, target_lang: &str, source_lang: Option<&str>) {
+        let record = AuditRecord {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            model,
+            source_lang,
+            target_lang,
+            char_count: text.chars().count(),
+            text_hash: format!("{:x}", Sha256::digest(text.as_bytes())),
+            text: self.full_text.then_some(text),
+        };
+
+        let line = serde_json::to_string(&record).expect("Audit record is always serializable");
+        if let Some(sender) = &self.sender {
+            *UNWRITTEN.lock().unwrap() += 1;
+            if sender.send(line).is_err() {
+                written();
+            }
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    /// Wait for records still queued to reach the file
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn write_records(mut file: File, records: mpsc::Receiver<String>) {
+    for record in records {
+        if let Err(e) = writeln!(file, "{}", record) {
+            eprintln!("Failed to write audit log: {}", e);
+        }
+        written();
+    }
+}
+
+fn written() {
+    *UNWRITTEN.lock().unwrap() -= 1;
+    WRITTEN.notify_all();
+}
+
+/// Wait until every queued record has been written.
+///
+/// `process::exit` never drops the audit log, so records still queued would be lost.
+pub fn flush() {
+    let mut unwritten = UNWRITTEN.lock().unwrap();
+    while *unwritten > 0 {
+        unwritten = WRITTEN.wait(unwritten).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    fn temp_path() -> PathBuf {
+        env::temp_dir().join(format!("tzh-audit-{}.jsonl", Uuid::new_v4()))
+    }
+
+    fn read_records(path: &Path) -> Vec<Value> {
+        let content = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn logs_a_hash_by_default() {
+        let path = temp_path();
+        let log = AuditLog::open(&path, false).unwrap();
+        log.record("deepseek-chat", "Hello", "zh", Some("en"));
+        drop(log);
+
+        let records = read_records(&path);
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record["model"], "deepseek-chat");
+        assert_eq!(record["source_lang"], "en");
+        assert_eq!(record["target_lang"], "zh");
+        assert_eq!(record["char_count"], 5);
+        assert_eq!(
+            record["text_hash"],
+            "185f8db32271fe25f561a6fc938b2e264306ec304eda518007d1764826381969"
+        );
+        assert!(record.get("text").is_none());
+    }
+
+    #[test]
+    fn logs_the_text_when_asked() {
+        let path = temp_path();
+        let log = AuditLog::open(&path, true).unwrap();
+        log.record("deepseek-chat", "你好", "en", None);
+        drop(log);
+
+        let records = read_records(&path);
+        assert_eq!(records[0]["text"], "你好");
+        assert_eq!(records[0]["char_count"], 2);
+        assert_eq!(records[0]["source_lang"], Value::Null);
+    }
+
+    #[test]
+    fn flush_waits_for_queued_records() {
+        let path = temp_path();
+        let log = AuditLog::open(&path, false).unwrap();
+        for _ in 0..100 {
+            log.record("deepseek-chat", "Hello", "zh", None);
+        }
+
+        // Without dropping the log, as when exiting the process
+        flush();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 100);
+        drop(log);
+        fs::remove_file(&path).unwrap();
+    }
+}
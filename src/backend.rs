@@ -0,0 +1,114 @@
+use anyhow::Result;
+use reqwest::StatusCode;
+use std::fmt;
+
+use crate::translator::Translation;
+
+/// Whether a request translates a single word or a line of text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextKind {
+    Word,
+    Line,
+}
+
+/// A service that performs a single translation attempt.
+///
+/// Retries and the fallback provider are handled by the `Translator` on top of this.
+// Only implemented in this crate, whose futures need not be `Send`
+#[allow(async_fn_in_trait)]
+pub trait Backend {
+    async fn translate(
+        &self,
+        text: &str,
+        kind: TextKind,
+        target_lang: &str,
+        source_lang: Option<&str>,
+        idempotency_key: &str,
+    ) -> Result<Translation>;
+
+    /// Whether the backend can be asked more firmly after an empty answer. Others
+    /// would only get the same request again, so empty answers aren't retried.
+    fn can_insist(&self) -> bool {
+        false
+    }
+}
+
+/// The service answered successfully but without any translated text
+#[derive(Debug)]
+pub struct EmptyResponse;
+
+impl fmt::Display for EmptyResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No translation returned from API")
+    }
+}
+
+impl std::error::Error for EmptyResponse {}
+
+/// The service answered with an error status
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl ApiError {
+    /// Whether the provider is too busy right now, which calls for more patient retries.
+    ///
+    /// Anthropic uses 529 for this and most others 503, and some say so only in the body
+    /// of another server error or a 429. The body of a client error, such as a prompt
+    /// that asks about capacity, says nothing about the provider.
+    pub fn is_overloaded(&self) -> bool {
+        if matches!(self.status.as_u16(), 503 | 529) {
+            return true;
+        }
+        if !(self.status.is_server_error() || self.status == StatusCode::TOO_MANY_REQUESTS) {
+            return false;
+        }
+        let body = self.body.to_lowercase();
+        body.contains("overloaded") || body.contains("capacity")
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "API request failed with status {}: {}",
+            self.status, self.body
+        )
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(status: u16, body: &str) -> ApiError {
+        ApiError {
+            status: StatusCode::from_u16(status).unwrap(),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn overload_statuses() {
+        assert!(error(529, "").is_overloaded());
+        assert!(error(503, "").is_overloaded());
+        assert!(!error(500, "boom").is_overloaded());
+    }
+
+    #[test]
+    fn overload_said_in_the_body_of_a_server_error_or_429() {
+        assert!(error(500, "Model is overloaded").is_overloaded());
+        assert!(error(429, "Insufficient capacity").is_overloaded());
+    }
+
+    #[test]
+    fn client_error_body_is_not_an_overload() {
+        assert!(!error(400, "Invalid prompt: overloaded").is_overloaded());
+        assert!(!error(404, "capacity").is_overloaded());
+    }
+}
@@ -0,0 +1,100 @@
+use serde::Serialize;
+
+/// System prompt asking for a translation and a definition in the word's own language
+pub const SYSTEM_PROMPT: &str = "You are a professional translator and lexicographer. Reply with exactly two lines:
+TRANSLATION: the common and distinct meanings of the word in the target language, separated by semicolons
+DEFINITION: a brief definition of the word in its own language, followed by a few synonyms
+Rules:
+- No pronunciation guides (no pinyin, romanization, or phonetic notation)
+- No quotes and no other text
+- For empty input, output nothing";
+
+/// A word with its translation and a definition in its own language, for flashcards
+#[derive(Debug, Serialize)]
+pub struct WordCard {
+    pub word: String,
+    pub translation: String,
+    pub definition: Option<String>,
+}
+
+impl WordCard {
+    /// Read a reply to `SYSTEM_PROMPT`.
+    ///
+    /// Without a `TRANSLATION:` label, as in replies from DeepL, the unlabelled
+    /// lines are taken as the translation. Labels are matched without regard to case.
+    pub fn parse(word: &str, reply: &str) -> Self {
+        let mut translation = None;
+        let mut definition = None;
+        let mut unlabelled = Vec::new();
+        for line in reply.lines() {
+            if let Some(value) = strip_label(line, "translation:") {
+                translation = Some(value);
+            } else if let Some(value) = strip_label(line, "definition:") {
+                definition = Some(value);
+            } else if !line.trim().is_empty() {
+                unlabelled.push(line.trim());
+            }
+        }
+
+        Self {
+            word: word.to_string(),
+            translation: translation.unwrap_or_else(|| unlabelled.join(" ")),
+            definition: definition.filter(|definition| !definition.is_empty()),
+        }
+    }
+}
+
+fn strip_label(line: &str, label: &str) -> Option<String> {
+    let line = line.trim();
+    let prefix = line.get(..label.len())?;
+    prefix
+        .eq_ignore_ascii_case(label)
+        .then(|| line[label.len()..].trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_labelled_lines() {
+        let card = WordCard::parse(
+            "bank",
+            "TRANSLATION: 银行; 河岸\nDEFINITION: a financial institution; shore",
+        );
+        assert_eq!(card.word, "bank");
+        assert_eq!(card.translation, "银行; 河岸");
+        assert_eq!(
+            card.definition.as_deref(),
+            Some("a financial institution; shore")
+        );
+    }
+
+    #[test]
+    fn labels_ignore_case_and_surrounding_space() {
+        let card = WordCard::parse(
+            "ice cream",
+            "  translation:  冰淇淋 \n\nDefinition: a frozen dessert",
+        );
+        assert_eq!(card.translation, "冰淇淋");
+        assert_eq!(card.definition.as_deref(), Some("a frozen dessert"));
+    }
+
+    #[test]
+    fn unlabelled_reply_is_the_translation() {
+        let card = WordCard::parse("Haus", "house\nhome");
+        assert_eq!(card.translation, "house home");
+        assert_eq!(card.definition, None);
+    }
+
+    #[test]
+    fn empty_definition_is_left_out() {
+        let card = WordCard::parse("cat", "TRANSLATION: 猫\nDEFINITION:");
+        assert_eq!(card.definition, None);
+        let json = serde_json::to_string(&card).unwrap();
+        assert_eq!(
+            json,
+            r#"{"word":"cat","translation":"猫","definition":null}"#
+        );
+    }
+}
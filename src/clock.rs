@@ -0,0 +1,40 @@
+use futures::future::BoxFuture;
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Waits between retries, so tests can substitute a clock that doesn't really sleep
+pub trait Sleeper: Send + Sync {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()>;
+}
+
+/// Sleeps on the tokio timer
+pub struct TokioSleeper;
+
+impl Sleeper for TokioSleeper {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Records each wait instead of sleeping, so tests can check retry delays
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct RecordingSleeper {
+    slept: Arc<Mutex<Vec<Duration>>>,
+}
+
+#[cfg(test)]
+impl RecordingSleeper {
+    pub fn slept(&self) -> Vec<Duration> {
+        self.slept.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Sleeper for RecordingSleeper {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        self.slept.lock().unwrap().push(duration);
+        Box::pin(futures::future::ready(()))
+    }
+}
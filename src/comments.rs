@@ -0,0 +1,365 @@
+use clap::ValueEnum;
+use std::path::Path;
+
+/// Source language whose comments are translated with --code-comments
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum CodeLanguage {
+    /// Infer from the file extension
+    Auto,
+    Rust,
+    /// C, C++, Java, Go and other C-style languages
+    C,
+    /// JavaScript and TypeScript
+    Js,
+    Python,
+}
+
+impl CodeLanguage {
+    /// Resolve `auto` from the extension of `path`
+    pub fn resolve(self, path: Option<&Path>) -> Option<Self> {
+        if self != CodeLanguage::Auto {
+            return Some(self);
+        }
+
+        let extension = path?.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "rs" => Some(CodeLanguage::Rust),
+            "c" | "h" | "cc" | "cpp" | "hpp" | "cxx" | "java" | "go" | "cs" | "swift" | "kt" => {
+                Some(CodeLanguage::C)
+            }
+            "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" => Some(CodeLanguage::Js),
+            "py" | "pyw" => Some(CodeLanguage::Python),
+            _ => None,
+        }
+    }
+}
+
+/// A run of source text, either kept verbatim or translated
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece {
+    Code(String),
+    Comment {
+        text: String,
+        /// Line of the source the comment starts on, counted from 0
+        line: usize,
+    },
+}
+
+/// Split source code into verbatim code and translatable comment text.
+///
+/// Comment markers, decorations and surrounding whitespace stay in `Code` pieces,
+/// so concatenating all pieces reproduces the input byte for byte.
+pub fn split(source: &str, language: CodeLanguage) -> Vec<Piece> {
+    let mut lexer = Lexer {
+        source,
+        pos: 0,
+        code_start: 0,
+        pieces: Vec::new(),
+    };
+
+    match language {
+        CodeLanguage::Python => lexer.lex_python(),
+        _ => lexer.lex_c_style(language),
+    }
+    lexer.pieces
+}
+
+/// Concatenate pieces back into source text
+pub fn join(pieces: &[Piece]) -> String {
+    pieces
+        .iter()
+        .map(|piece| match piece {
+            Piece::Code(text) | Piece::Comment { text, .. } => text.as_str(),
+        })
+        .collect()
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    pos: usize,
+    /// Start of the code not yet pushed as a piece
+    code_start: usize,
+    pieces: Vec<Piece>,
+}
+
+impl<'a> Lexer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn lex_c_style(&mut self, language: CodeLanguage) {
+        while let Some(c) = self.peek() {
+            let rest = self.rest();
+            if rest.starts_with("//") {
+                self.line_comment(&["///", "//!", "//"]);
+            } else if rest.starts_with("/*") {
+                self.block_comment(language == CodeLanguage::Rust);
+            } else if c == '"' || (c == '`' && language == CodeLanguage::Js) {
+                self.string(c);
+            } else if language == CodeLanguage::Rust
+                && (rest.starts_with("r\"") || rest.starts_with("r#"))
+            {
+                self.raw_string();
+            } else if c == '\'' {
+                self.char_literal(language);
+            } else {
+                self.bump();
+            }
+        }
+        self.flush_code(self.pos);
+    }
+
+    fn lex_python(&mut self) {
+        while let Some(c) = self.peek() {
+            let rest = self.rest();
+            if c == '#' {
+                self.line_comment(&["#"]);
+            } else if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+                let quote = &rest[..3];
+                self.pos += 3;
+                match self.rest().find(quote) {
+                    Some(end) => self.pos += end + 3,
+                    None => self.pos = self.source.len(),
+                }
+            } else if c == '"' || c == '\'' {
+                self.string(c);
+            } else {
+                self.bump();
+            }
+        }
+        self.flush_code(self.pos);
+    }
+
+    fn flush_code(&mut self, end: usize) {
+        if end > self.code_start {
+            self.pieces
+                .push(Piece::Code(self.source[self.code_start..end].to_string()));
+        }
+        self.code_start = end;
+    }
+
+    /// Push comment text, keeping its surrounding whitespace as code
+    fn push_comment(&mut self, start: usize, end: usize) {
+        let text = &self.source[start..end];
+        let body_start = start + (text.len() - text.trim_start().len());
+        let body_end = start + text.trim_end().len();
+
+        if body_start >= body_end {
+            return;
+        }
+
+        self.flush_code(body_start);
+        self.pieces.push(Piece::Comment {
+            text: self.source[body_start..body_end].to_string(),
+            line: self.source[..body_start].matches('\n').count(),
+        });
+        self.code_start = body_end;
+    }
+
+    fn line_comment(&mut self, markers: &[&str]) {
+        let marker = markers
+            .iter()
+            .find(|marker| self.rest().starts_with(**marker))
+            .map_or(0, |marker| marker.len());
+        self.pos += marker;
+
+        let end = self
+            .rest()
+            .find('\n')
+            .map_or(self.source.len(), |offset| self.pos + offset);
+        self.push_comment(self.pos, end);
+        self.pos = end;
+    }
+
+    fn block_comment(&mut self, nested: bool) {
+        self.pos += 2;
+        let mut depth = 1;
+        let mut line_start = self.pos;
+
+        // Skip the `*` decoration of doc comments such as `/**`
+        while self.rest().starts_with('*') && !self.rest().starts_with("*/") {
+            self.pos += 1;
+            line_start = self.pos;
+        }
+
+        while let Some(c) = self.peek() {
+            let rest = self.rest();
+            if rest.starts_with("*/") {
+                depth -= 1;
+                if depth == 0 {
+                    self.push_comment(line_start, self.pos);
+                    self.pos += 2;
+                    return;
+                }
+                self.pos += 2;
+            } else if nested && rest.starts_with("/*") {
+                depth += 1;
+                self.pos += 2;
+            } else if c == '\n' {
+                self.push_comment(line_start, self.pos);
+                self.bump();
+
+                // Leading whitespace and `*` decorations stay as code
+                let decoration = self
+                    .rest()
+                    .chars()
+                    .take_while(|c| *c == ' ' || *c == '\t')
+                    .count();
+                self.pos += decoration;
+                if self.rest().starts_with('*') && !self.rest().starts_with("*/") {
+                    self.pos += 1;
+                }
+                line_start = self.pos;
+            } else {
+                self.bump();
+            }
+        }
+
+        // Unterminated comment runs to the end of the file
+        self.push_comment(line_start, self.pos);
+    }
+
+    fn string(&mut self, quote: char) {
+        self.bump();
+        while let Some(c) = self.peek() {
+            self.bump();
+            if c == '\\' {
+                self.bump();
+            } else if c == quote {
+                return;
+            }
+        }
+    }
+
+    fn raw_string(&mut self) {
+        self.pos += 1;
+        let hashes = self.rest().chars().take_while(|c| *c == '#').count();
+        if !self.rest()[hashes..].starts_with('"') {
+            // An identifier starting with `r`, not a raw string
+            return;
+        }
+
+        self.pos += hashes + 1;
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        match self.rest().find(&terminator) {
+            Some(end) => self.pos += end + terminator.len(),
+            None => self.pos = self.source.len(),
+        }
+    }
+
+    fn char_literal(&mut self, language: CodeLanguage) {
+        let rest = self.rest();
+        let mut chars = rest.char_indices().skip(1);
+
+        // In Rust a quote not closed right after one character starts a lifetime
+        let closes = match chars.next() {
+            Some((_, '\\')) => true,
+            Some((_, _)) => matches!(chars.next(), Some((_, '\''))),
+            None => false,
+        };
+
+        if language != CodeLanguage::Rust || closes {
+            self.string('\'');
+        } else {
+            self.bump();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = r#"//! Crate docs
+/// Adds one
+fn add_one(x: i32) -> i32 {
+    let s = "// not a comment";
+    let c = '"';
+    x + 1 // increment
+}
+
+/* Block
+ * comment */
+fn lifetime<'a>(s: &'a str) -> &'a str { s }
+"#;
+
+    fn comments(pieces: &[Piece]) -> Vec<(&str, usize)> {
+        pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Comment { text, line } => Some((text.as_str(), *line)),
+                Piece::Code(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_rust_comments_with_their_lines() {
+        let pieces = split(RUST, CodeLanguage::Rust);
+        assert_eq!(
+            comments(&pieces),
+            [
+                ("Crate docs", 0),
+                ("Adds one", 1),
+                ("increment", 5),
+                ("Block", 8),
+                ("comment", 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn joining_reproduces_the_source() {
+        for language in [CodeLanguage::Rust, CodeLanguage::C, CodeLanguage::Js] {
+            assert_eq!(join(&split(RUST, language)), RUST);
+        }
+    }
+
+    #[test]
+    fn only_comments_change() {
+        let mut pieces = split(RUST, CodeLanguage::Rust);
+        for piece in &mut pieces {
+            if let Piece::Comment { text, .. } = piece {
+                *text = text.to_uppercase();
+            }
+        }
+        let translated = join(&pieces);
+
+        assert!(translated.starts_with("//! CRATE DOCS\n/// ADDS ONE\nfn add_one"));
+        assert!(translated.contains("let s = \"// not a comment\";"));
+        assert!(translated.contains("x + 1 // INCREMENT\n"));
+        assert!(translated.contains("/* BLOCK\n * COMMENT */\n"));
+    }
+
+    #[test]
+    fn python_comments_skip_strings() {
+        let source = "x = '# no'  # yes\n# second\n";
+        let pieces = split(source, CodeLanguage::Python);
+        assert_eq!(comments(&pieces), [("yes", 0), ("second", 1)]);
+        assert_eq!(join(&pieces), source);
+    }
+
+    #[test]
+    fn resolves_the_language_from_the_extension() {
+        let resolve = |path: &str| CodeLanguage::Auto.resolve(Some(Path::new(path)));
+        assert_eq!(resolve("main.rs"), Some(CodeLanguage::Rust));
+        assert_eq!(resolve("app.tsx"), Some(CodeLanguage::Js));
+        assert_eq!(resolve("lib.h"), Some(CodeLanguage::C));
+        assert_eq!(resolve("tool.py"), Some(CodeLanguage::Python));
+        assert_eq!(resolve("notes.txt"), None);
+        assert_eq!(
+            CodeLanguage::C.resolve(Some(Path::new("main.rs"))),
+            Some(CodeLanguage::C)
+        );
+    }
+}
@@ -1,38 +1,160 @@
 use anyhow::{Context, Result};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::theme::{Colors, Theme};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    pub backend: BackendKind,
     pub endpoint: String,
     pub api_key: Option<String>,
     pub model: String,
     pub timeout: u64,
     pub temperature: f32,
     pub max_tokens: Option<i32>,
+    /// Regex whose first capture group extracts the translation from the raw response
+    pub answer_extractor: Option<String>,
+    /// Mark the system prompt as cacheable for providers that support prompt caching
+    /// markers (Anthropic and OpenRouter); other providers get plain requests
+    pub prompt_caching: bool,
+    /// Expand unset `${VAR}` references to an empty string instead of failing
+    pub allow_missing_env: bool,
+    pub fallback: Option<FallbackConfig>,
+    pub pricing: Option<Pricing>,
+    /// Refuse runs estimated to cost more than this, and stop once they actually do
+    pub max_cost_per_run: Option<f64>,
+    /// Add bidi control characters around right-to-left translations shown on a terminal
+    pub bidi_control: bool,
+    /// Show formatted translate output in a pager, as with --pager
+    pub pager: bool,
+    /// Accept gzip, brotli and deflate compressed responses; turn off to debug a proxy
+    pub compression: bool,
+    /// Retries after a provider reports being overloaded (503, 529), on top of the usual ones
+    pub overload_max_retries: u32,
+    /// Seconds before the first retry after an overload, doubled for each further one
+    pub overload_base_delay: u64,
+    /// File that gets a JSON line for every request sent, with a hash of the text
+    pub audit_log: Option<PathBuf>,
+    /// Color names for each kind of terminal output
+    pub colors: Colors,
+    /// Original `${VAR}` values, restored when saving so secrets never hit the disk
+    #[serde(skip)]
+    env_templates: Vec<EnvTemplate>,
+}
+
+/// Config values that may reference environment variables
+#[derive(Debug, Clone, Copy)]
+enum EnvField {
+    Endpoint,
+    ApiKey,
+    Model,
+    FallbackEndpoint,
+    FallbackModel,
+    FallbackApiKey,
+}
+
+const ENV_FIELDS: [EnvField; 6] = [
+    EnvField::Endpoint,
+    EnvField::ApiKey,
+    EnvField::Model,
+    EnvField::FallbackEndpoint,
+    EnvField::FallbackModel,
+    EnvField::FallbackApiKey,
+];
+
+#[derive(Debug, Clone)]
+struct EnvTemplate {
+    field: EnvField,
+    template: String,
+    expanded: String,
+}
+
+/// Translation service the endpoint belongs to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// OpenAI-compatible chat completions
+    #[default]
+    Chat,
+    /// DeepL translation API
+    Deepl,
+}
+
+/// Secondary provider tried once after the primary exhausts its retries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallbackConfig {
+    pub endpoint: String,
+    pub model: String,
+    pub api_key: Option<String>,
+}
+
+/// Model prices per million tokens, used to estimate the cost of a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pricing {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl Pricing {
+    pub fn cost(&self, input_tokens: usize, output_tokens: usize) -> f64 {
+        (input_tokens as f64 * self.input + output_tokens as f64 * self.output) / 1_000_000.0
+    }
 }
 
 // Partial config struct for loading from file with missing fields
 #[derive(Debug, Deserialize)]
 struct PartialConfig {
+    backend: Option<BackendKind>,
     endpoint: Option<String>,
     api_key: Option<String>,
     model: Option<String>,
     timeout: Option<u64>,
     temperature: Option<f32>,
     max_tokens: Option<Option<i32>>,
+    answer_extractor: Option<String>,
+    prompt_caching: Option<bool>,
+    allow_missing_env: Option<bool>,
+    fallback: Option<FallbackConfig>,
+    pricing: Option<Pricing>,
+    max_cost_per_run: Option<f64>,
+    bidi_control: Option<bool>,
+    pager: Option<bool>,
+    compression: Option<bool>,
+    overload_max_retries: Option<u32>,
+    overload_base_delay: Option<u64>,
+    audit_log: Option<PathBuf>,
+    colors: Option<Colors>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            backend: BackendKind::Chat,
             endpoint: "https://api.deepseek.com/v1".to_string(),
             api_key: None,
             model: "deepseek-chat".to_string(),
             timeout: 30,
             temperature: 1.3,
             max_tokens: Some(2000),
+            answer_extractor: None,
+            prompt_caching: false,
+            allow_missing_env: false,
+            fallback: None,
+            pricing: None,
+            max_cost_per_run: None,
+            bidi_control: true,
+            pager: false,
+            compression: true,
+            overload_max_retries: 6,
+            overload_base_delay: 5,
+            audit_log: None,
+            colors: Colors::default(),
+            env_templates: Vec::new(),
         }
     }
 }
@@ -49,17 +171,46 @@ impl Config {
                 toml::from_str(&content).context("Failed to parse config file")?;
 
             let default = Config::default();
-            let config = Config {
+            let mut config = Config {
+                backend: partial.backend.unwrap_or(default.backend),
                 endpoint: partial.endpoint.unwrap_or(default.endpoint),
                 api_key: partial.api_key.or(default.api_key),
                 model: partial.model.unwrap_or(default.model),
                 timeout: partial.timeout.unwrap_or(default.timeout),
                 temperature: partial.temperature.unwrap_or(default.temperature),
                 max_tokens: partial.max_tokens.unwrap_or(default.max_tokens),
+                answer_extractor: partial.answer_extractor.or(default.answer_extractor),
+                prompt_caching: partial.prompt_caching.unwrap_or(default.prompt_caching),
+                allow_missing_env: partial
+                    .allow_missing_env
+                    .unwrap_or(default.allow_missing_env),
+                fallback: partial.fallback.or(default.fallback),
+                pricing: partial.pricing.or(default.pricing),
+                max_cost_per_run: partial.max_cost_per_run.or(default.max_cost_per_run),
+                bidi_control: partial.bidi_control.unwrap_or(default.bidi_control),
+                pager: partial.pager.unwrap_or(default.pager),
+                compression: partial.compression.unwrap_or(default.compression),
+                overload_max_retries: partial
+                    .overload_max_retries
+                    .unwrap_or(default.overload_max_retries),
+                overload_base_delay: partial
+                    .overload_base_delay
+                    .unwrap_or(default.overload_base_delay),
+                audit_log: partial.audit_log.or(default.audit_log),
+                colors: partial.colors.unwrap_or(default.colors),
+                env_templates: Vec::new(),
             };
 
-            // Save the merged config to ensure all fields are present in the file
-            config.save()?;
+            config.expand_env_vars()?;
+            config.validate()?;
+
+            // Add missing fields to the file, but leave a complete one untouched so
+            // its formatting, comments and permissions are kept
+            let on_disk: toml::Value = toml::from_str(&content)?;
+            let merged: toml::Value = toml::from_str(&config.to_toml()?)?;
+            if merged != on_disk {
+                config.save()?;
+            }
             Ok(config)
         } else {
             // Create default config
@@ -76,19 +227,113 @@ impl Config {
             fs::create_dir_all(parent)?;
         }
 
-        let content = toml::to_string_pretty(self)?;
-        fs::write(&config_path, content)?;
+        fs::write(&config_path, self.to_toml()?)?;
         Ok(())
     }
 
-    fn config_path() -> Result<PathBuf> {
-        let config_dir = dirs::config_dir()
+    /// The config file content for this config
+    fn to_toml(&self) -> Result<String> {
+        // Write back the `${VAR}` references rather than their expanded values
+        let mut on_disk = self.clone();
+        for template in &self.env_templates {
+            if let Some(value) = on_disk.env_field(template.field)
+                && *value == template.expanded
+            {
+                *value = template.template.clone();
+            }
+        }
+
+        Ok(toml::to_string_pretty(&on_disk)?)
+    }
+
+    /// Replace `${VAR}` references in string values with the environment variable
+    fn expand_env_vars(&mut self) -> Result<()> {
+        let allow_missing = self.allow_missing_env;
+
+        for field in ENV_FIELDS {
+            if let Some(value) = self.env_field(field)
+                && value.contains("${")
+            {
+                let template = value.clone();
+                let expanded = expand_env(&template, allow_missing)?;
+                *value = expanded.clone();
+                self.env_templates.push(EnvTemplate {
+                    field,
+                    template,
+                    expanded,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn env_field(&mut self, field: EnvField) -> Option<&mut String> {
+        match field {
+            EnvField::Endpoint => Some(&mut self.endpoint),
+            EnvField::ApiKey => self.api_key.as_mut(),
+            EnvField::Model => Some(&mut self.model),
+            EnvField::FallbackEndpoint => self.fallback.as_mut().map(|f| &mut f.endpoint),
+            EnvField::FallbackModel => self.fallback.as_mut().map(|f| &mut f.model),
+            EnvField::FallbackApiKey => self.fallback.as_mut().and_then(|f| f.api_key.as_mut()),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.colors.validate()?;
+
+        if self.max_cost_per_run.is_some() && self.pricing.is_none() {
+            anyhow::bail!("max_cost_per_run needs a [pricing] table to estimate costs");
+        }
+
+        if let Some(pattern) = &self.answer_extractor {
+            let regex = Regex::new(pattern).context("Invalid answer_extractor regex")?;
+            if regex.captures_len() < 2 {
+                anyhow::bail!("answer_extractor regex must contain a capture group");
+            }
+        }
+        Ok(())
+    }
+
+    /// One-line `key=value` summary of the settings a request uses, with the API key redacted
+    pub fn summary(&self) -> String {
+        format!(
+            "backend={} endpoint={} model={} temperature={} max_tokens={} api_key={}",
+            format!("{:?}", self.backend).to_lowercase(),
+            self.endpoint,
+            self.model,
+            self.temperature,
+            self.max_tokens
+                .map_or_else(|| "unlimited".to_string(), |t| t.to_string()),
+            if self.has_api_key() {
+                "<redacted>"
+            } else {
+                "<none>"
+            }
+        )
+    }
+
+    /// Last modification time of the config file, if it exists
+    pub fn modified() -> Option<SystemTime> {
+        let config_path = Self::config_path().ok()?;
+        fs::metadata(config_path).ok()?.modified().ok()
+    }
+
+    /// Directory holding the config file and presets
+    pub fn dir() -> Result<PathBuf> {
+        Ok(dirs::config_dir()
             .context("Failed to get config directory")?
-            .join("tzh");
-        Ok(config_dir.join("config.toml"))
+            .join("tzh"))
+    }
+
+    fn config_path() -> Result<PathBuf> {
+        Ok(Self::dir()?.join("config.toml"))
     }
 
     // Getters
+    pub fn backend(&self) -> BackendKind {
+        self.backend
+    }
+
     pub fn endpoint(&self) -> &str {
         &self.endpoint
     }
@@ -113,11 +358,75 @@ impl Config {
         self.max_tokens
     }
 
+    pub fn answer_extractor(&self) -> Option<&str> {
+        self.answer_extractor.as_deref()
+    }
+
+    pub fn prompt_caching(&self) -> bool {
+        self.prompt_caching
+    }
+
+    pub fn fallback(&self) -> Option<&FallbackConfig> {
+        self.fallback.as_ref()
+    }
+
+    pub fn bidi_control(&self) -> bool {
+        self.bidi_control
+    }
+
+    pub fn pricing(&self) -> Option<&Pricing> {
+        self.pricing.as_ref()
+    }
+
+    pub fn max_cost_per_run(&self) -> Option<f64> {
+        self.max_cost_per_run
+    }
+
+    pub fn pager(&self) -> bool {
+        self.pager
+    }
+
+    pub fn compression(&self) -> bool {
+        self.compression
+    }
+
+    pub fn overload_max_retries(&self) -> u32 {
+        self.overload_max_retries
+    }
+
+    pub fn overload_base_delay(&self) -> u64 {
+        self.overload_base_delay
+    }
+
+    pub fn audit_log(&self) -> Option<&Path> {
+        self.audit_log.as_deref()
+    }
+
+    pub fn colors(&self) -> &Colors {
+        &self.colors
+    }
+
+    /// API key of the configured provider at `endpoint`, so no other endpoint ever sees it
+    pub fn api_key_for(&self, endpoint: &str) -> Option<&str> {
+        let endpoint = endpoint.trim_end_matches('/');
+        if self.endpoint.trim_end_matches('/') == endpoint {
+            return self.api_key();
+        }
+        self.fallback
+            .as_ref()
+            .filter(|fallback| fallback.endpoint.trim_end_matches('/') == endpoint)
+            .and_then(|fallback| fallback.api_key.as_deref())
+    }
+
     pub fn has_api_key(&self) -> bool {
         self.api_key.is_some() && !self.api_key.as_ref().unwrap().is_empty()
     }
 
     // Setters
+    pub fn set_backend(&mut self, backend: BackendKind) {
+        self.backend = backend;
+    }
+
     pub fn set_endpoint(&mut self, endpoint: &str) {
         self.endpoint = endpoint.to_string();
     }
@@ -137,4 +446,131 @@ impl Config {
     pub fn set_max_tokens(&mut self, max_tokens: Option<i32>) {
         self.max_tokens = max_tokens;
     }
+
+    pub fn set_max_cost_per_run(&mut self, max_cost: Option<f64>) {
+        self.max_cost_per_run = max_cost;
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.colors = Colors::preset(theme);
+    }
+
+    pub fn set_prompt_caching(&mut self, prompt_caching: bool) {
+        self.prompt_caching = prompt_caching;
+    }
+}
+
+/// Expand every `${VAR}` in a value, failing on unset variables unless allowed
+fn expand_env(value: &str, allow_missing: bool) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+
+        let name = &rest[start + 2..start + 2 + len];
+        expanded.push_str(&rest[..start]);
+        match env::var(name) {
+            Ok(var) => expanded.push_str(&var),
+            Err(_) if allow_missing => {}
+            Err(_) => anyhow::bail!(
+                "Environment variable {} referenced in config is not set",
+                name
+            ),
+        }
+        rest = &rest[start + 3 + len..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answer_extractor_needs_a_valid_pattern_with_a_group() {
+        let mut config = Config {
+            answer_extractor: Some("Answer: (.*)".to_string()),
+            ..Config::default()
+        };
+        config.validate().unwrap();
+
+        config.answer_extractor = Some("Answer: .*".to_string());
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("capture group"), "{}", error);
+
+        config.answer_extractor = Some("Answer: (".to_string());
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("Invalid"), "{}", error);
+    }
+
+    #[test]
+    fn expands_environment_variables_in_values() {
+        let home = env::var("HOME").unwrap();
+        assert_eq!(
+            expand_env("${HOME}/keys:${HOME}", false).unwrap(),
+            format!("{home}/keys:{home}")
+        );
+        assert_eq!(expand_env("no variables", false).unwrap(), "no variables");
+        assert_eq!(expand_env("open ${HOME", false).unwrap(), "open ${HOME");
+    }
+
+    #[test]
+    fn unset_variables_fail_unless_allowed() {
+        let error = expand_env("key-${TZH_TEST_UNSET}", false).unwrap_err();
+        assert!(error.to_string().contains("TZH_TEST_UNSET"), "{}", error);
+        assert_eq!(expand_env("key-${TZH_TEST_UNSET}", true).unwrap(), "key-");
+    }
+
+    #[test]
+    fn references_are_written_back_instead_of_values() {
+        let mut config = Config::default();
+        config.set_api_key("${HOME}");
+        config.expand_env_vars().unwrap();
+        assert_eq!(config.api_key(), Some(env::var("HOME").unwrap().as_str()));
+
+        let saved = config.to_toml().unwrap();
+        assert!(saved.contains("api_key = \"${HOME}\""), "{}", saved);
+    }
+
+    #[test]
+    fn changed_values_are_written_as_they_are() {
+        let mut config = Config::default();
+        config.set_api_key("${HOME}");
+        config.expand_env_vars().unwrap();
+        config.set_api_key("literal");
+        assert!(config.to_toml().unwrap().contains("api_key = \"literal\""));
+    }
+
+    #[test]
+    fn summary_redacts_the_api_key() {
+        let mut config = Config::default();
+        config.set_endpoint("https://api.example.com");
+        config.set_model("model-1");
+        config.set_max_tokens(None);
+        assert!(
+            config
+                .summary()
+                .ends_with("max_tokens=unlimited api_key=<none>")
+        );
+
+        config.set_api_key("sk-secret");
+        config.set_max_tokens(Some(100));
+        let summary = config.summary();
+        assert!(
+            summary.starts_with("backend=chat endpoint=https://api.example.com model=model-1 "),
+            "{}",
+            summary
+        );
+        assert!(
+            summary.ends_with("max_tokens=100 api_key=<redacted>"),
+            "{}",
+            summary
+        );
+        assert!(!summary.contains("sk-secret"));
+    }
 }
@@ -0,0 +1,230 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::backend::{ApiError, Backend, EmptyResponse, TextKind};
+use crate::config::Config;
+use crate::debug_bundle::{self, DebugBundle};
+use crate::translator::Translation;
+
+#[derive(Debug, Serialize)]
+struct DeepLRequest<'a> {
+    text: Vec<&'a str>,
+    target_lang: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_lang: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeepLResponse {
+    translations: Vec<DeepLTranslation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeepLTranslation {
+    text: String,
+}
+
+/// The DeepL `/v2/translate` API, using the configured endpoint and API key
+pub struct DeepLBackend {
+    client: Client,
+    endpoint: String,
+    api_key: Option<String>,
+    debug_bundle: Option<Arc<DebugBundle>>,
+}
+
+impl DeepLBackend {
+    pub fn new(client: Client, config: &Config) -> Self {
+        Self {
+            client,
+            endpoint: config.endpoint().trim_end_matches('/').to_string(),
+            api_key: config.api_key().map(str::to_string),
+            debug_bundle: None,
+        }
+    }
+
+    pub fn set_debug_bundle(&mut self, bundle: Arc<DebugBundle>) {
+        self.debug_bundle = Some(bundle);
+    }
+}
+
+impl Backend for DeepLBackend {
+    async fn translate(
+        &self,
+        text: &str,
+        _kind: TextKind,
+        target_lang: &str,
+        source_lang: Option<&str>,
+        idempotency_key: &str,
+    ) -> Result<Translation> {
+        let request = DeepLRequest {
+            text: vec![text],
+            target_lang: target_lang_code(target_lang),
+            source_lang: source_lang.map(source_lang_code),
+        };
+
+        let url = format!("{}/translate", self.endpoint);
+        let mut req_builder = self
+            .client
+            .post(&url)
+            .header("X-Idempotency-Key", idempotency_key)
+            .json(&request);
+
+        if let Some(api_key) = &self.api_key
+            && !api_key.is_empty()
+        {
+            req_builder =
+                req_builder.header("Authorization", format!("DeepL-Auth-Key {}", api_key));
+        }
+
+        let (status, body) =
+            debug_bundle::execute(&self.client, req_builder, self.debug_bundle.as_deref()).await?;
+
+        if !status.is_success() {
+            return Err(ApiError { status, body }.into());
+        }
+
+        let deepl_response: DeepLResponse =
+            serde_json::from_str(&body).context("Failed to parse API response")?;
+
+        let translation = deepl_response
+            .translations
+            .into_iter()
+            .next()
+            .ok_or(EmptyResponse)?;
+        if translation.text.trim().is_empty() {
+            return Err(EmptyResponse.into());
+        }
+
+        Ok(Translation::new(translation.text.trim()))
+    }
+}
+
+/// DeepL target language code, which distinguishes some regional variants
+fn target_lang_code(code: &str) -> String {
+    match code {
+        "zh" | "zh-cn" => "ZH-HANS".to_string(),
+        "zh-tw" => "ZH-HANT".to_string(),
+        "en" => "EN-US".to_string(),
+        "pt" => "PT-BR".to_string(),
+        _ => code.to_uppercase(),
+    }
+}
+
+/// DeepL source language code, which never includes a regional variant
+fn source_lang_code(code: &str) -> String {
+    code.split('-').next().unwrap_or(code).to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendKind;
+    use crate::translator::Translator;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn deepl_config(server: &MockServer) -> Config {
+        let mut config = Config::default();
+        config.set_backend(BackendKind::Deepl);
+        config.set_endpoint(&format!("{}/v2/", server.uri()));
+        config.set_api_key("deepl-key");
+        config
+    }
+
+    #[test]
+    fn maps_language_codes() {
+        assert_eq!(target_lang_code("zh"), "ZH-HANS");
+        assert_eq!(target_lang_code("zh-tw"), "ZH-HANT");
+        assert_eq!(target_lang_code("en"), "EN-US");
+        assert_eq!(target_lang_code("de"), "DE");
+        assert_eq!(source_lang_code("en-gb"), "EN");
+        assert_eq!(source_lang_code("zh-tw"), "ZH");
+    }
+
+    #[tokio::test]
+    async fn translates_through_the_deepl_api() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v2/translate"))
+            .and(header("Authorization", "DeepL-Auth-Key deepl-key"))
+            .and(body_json(json!({
+                "text": ["Hello"],
+                "target_lang": "ZH-HANS",
+                "source_lang": "EN"
+            })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "translations": [{ "text": " 你好 " }] })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&deepl_config(&server));
+        let mut output = Translation::default();
+        translator
+            .translate_line("Hello", "zh", Some("en"), |_, translation| {
+                output = translation.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(output.text, "你好");
+    }
+
+    #[tokio::test]
+    async fn error_status_is_an_api_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(456).set_body_string("Quota exceeded"))
+            .mount(&server)
+            .await;
+        let backend = DeepLBackend::new(Client::new(), &deepl_config(&server));
+
+        let error = backend
+            .translate("Hello", TextKind::Line, "de", None, "key")
+            .await
+            .unwrap_err();
+        let error = error.downcast::<ApiError>().unwrap();
+        assert_eq!(error.status.as_u16(), 456);
+        assert_eq!(error.body, "Quota exceeded");
+    }
+
+    #[tokio::test]
+    async fn missing_translation_is_empty() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "translations": [] })))
+            .mount(&server)
+            .await;
+        let backend = DeepLBackend::new(Client::new(), &deepl_config(&server));
+
+        let error = backend
+            .translate("Hello", TextKind::Line, "de", None, "key")
+            .await
+            .unwrap_err();
+        assert!(error.is::<EmptyResponse>());
+    }
+
+    #[tokio::test]
+    async fn empty_translation_is_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "translations": [{ "text": " " }] })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // DeepL has no prompt to insist with, so asking again gives the same answer
+        let translator = Translator::new(&deepl_config(&server));
+        let result = translator
+            .translate_line("Hello", "de", None, |_, _| {})
+            .await;
+        assert!(result.unwrap_err().is::<EmptyResponse>());
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+
+/// A line, or one field of a delimited line, that failed to translate
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FailedRow {
+    /// Line number, counted from 1
+    pub line: usize,
+    /// Field number with --translate-fields, counted from 1
+    pub field: Option<u64>,
+    pub source: String,
+    pub error: String,
+}
+
+/// CSV file collecting the rows that failed under --keep-going.
+///
+/// Each row is flushed right away, so the report is complete even when the run is
+/// aborted by --max-line-failures.
+pub struct ErrorReport {
+    writer: csv::Writer<File>,
+}
+
+impl ErrorReport {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        // Write the header up front so a run without failures leaves an empty report
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(file);
+        writer.write_record(["line", "field", "source", "error"])?;
+        writer.flush()?;
+        Ok(Self { writer })
+    }
+
+    pub fn record(&mut self, row: &FailedRow) -> Result<()> {
+        self.writer.serialize(row)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Read back the rows of a report written by `ErrorReport`
+pub fn read(path: &Path) -> Result<Vec<FailedRow>> {
+    let mut reader = csv::Reader::from_path(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    reader
+        .deserialize()
+        .collect::<Result<Vec<FailedRow>, _>>()
+        .with_context(|| format!("Failed to parse error report {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn report_path() -> std::path::PathBuf {
+        env::temp_dir().join(format!("tzh-errors-{}.csv", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn rows_are_read_back_as_written() {
+        let path = report_path();
+        let mut report = ErrorReport::create(&path).unwrap();
+        report
+            .record(&FailedRow {
+                line: 2,
+                field: None,
+                source: "Hello, \"world\"\nagain".to_string(),
+                error: "status 500".to_string(),
+            })
+            .unwrap();
+        report
+            .record(&FailedRow {
+                line: 5,
+                field: Some(3),
+                source: "Bye".to_string(),
+                error: "timed out".to_string(),
+            })
+            .unwrap();
+
+        let rows = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0].line, rows[0].field, rows[0].source.as_str()),
+            (2, None, "Hello, \"world\"\nagain")
+        );
+        assert_eq!(rows[0].error, "status 500");
+        assert_eq!((rows[1].line, rows[1].field), (5, Some(3)));
+    }
+
+    #[test]
+    fn report_without_failures_has_only_the_header() {
+        let path = report_path();
+        drop(ErrorReport::create(&path).unwrap());
+        let contents = fs::read_to_string(&path).unwrap();
+        let rows = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(contents, "line,field,source,error\n");
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn missing_report_fails() {
+        let error = read(&report_path()).unwrap_err();
+        assert!(error.to_string().starts_with("Failed to read"), "{error}");
+    }
+}
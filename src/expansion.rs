@@ -0,0 +1,126 @@
+use serde::Serialize;
+
+/// Character counts of one translated line
+#[derive(Debug, Serialize)]
+pub struct LineExpansion {
+    pub line: usize,
+    pub original_chars: usize,
+    pub translation_chars: usize,
+    /// Translation length divided by original length
+    pub ratio: f64,
+    /// The ratio is above the threshold, so the text may not fit where the original did
+    pub over_threshold: bool,
+}
+
+/// Per-line and overall length ratios of a translation
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub lines: Vec<LineExpansion>,
+    pub original_chars: usize,
+    pub translation_chars: usize,
+    pub ratio: f64,
+    pub threshold: f64,
+    /// Number of lines over the threshold
+    pub over_threshold: usize,
+}
+
+/// Compare the length in characters of each translation with its original.
+///
+/// Lines are numbered in output order; blank originals count as lines but are not
+/// measured. The overall ratio is computed from the summed lengths, so long lines
+/// weigh more than short ones.
+pub fn measure(pairs: &[(String, String)], threshold: f64) -> Report {
+    let mut lines = Vec::new();
+    let mut original_total = 0;
+    let mut translation_total = 0;
+
+    for (index, (original, translation)) in pairs.iter().enumerate() {
+        let original_chars = original.trim().chars().count();
+        if original_chars == 0 {
+            continue;
+        }
+        let translation_chars = translation.trim().chars().count();
+        let ratio = translation_chars as f64 / original_chars as f64;
+
+        lines.push(LineExpansion {
+            line: index + 1,
+            original_chars,
+            translation_chars,
+            ratio,
+            over_threshold: ratio > threshold,
+        });
+        original_total += original_chars;
+        translation_total += translation_chars;
+    }
+
+    Report {
+        over_threshold: lines.iter().filter(|line| line.over_threshold).count(),
+        lines,
+        original_chars: original_total,
+        translation_chars: translation_total,
+        ratio: if original_total == 0 {
+            0.0
+        } else {
+            translation_total as f64 / original_total as f64
+        },
+        threshold,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(original, translation)| (original.to_string(), translation.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn measures_each_line_in_characters() {
+        let report = measure(&pairs(&[("Save", "Speichern"), ("Über", "About")]), 1.5);
+        assert_eq!(report.lines.len(), 2);
+        assert_eq!(
+            (
+                report.lines[0].original_chars,
+                report.lines[0].translation_chars
+            ),
+            (4, 9)
+        );
+        assert_eq!(report.lines[0].ratio, 2.25);
+        assert!(report.lines[0].over_threshold);
+        // Counted in characters, not bytes
+        assert_eq!(report.lines[1].original_chars, 4);
+        assert!(!report.lines[1].over_threshold);
+        assert_eq!(report.over_threshold, 1);
+    }
+
+    #[test]
+    fn blank_lines_are_numbered_but_not_measured() {
+        let report = measure(
+            &pairs(&[("One", "Eins"), ("  ", ""), ("Two", " Zwei ")]),
+            2.0,
+        );
+        let numbers: Vec<usize> = report.lines.iter().map(|line| line.line).collect();
+        assert_eq!(numbers, [1, 3]);
+        assert_eq!(report.lines[1].translation_chars, 4);
+    }
+
+    #[test]
+    fn overall_ratio_weighs_lines_by_length() {
+        let report = measure(&pairs(&[("a", "abc"), ("abcdefghi", "abcdefghi")]), 2.0);
+        assert_eq!((report.original_chars, report.translation_chars), (10, 12));
+        assert_eq!(report.ratio, 1.2);
+        assert_eq!(report.threshold, 2.0);
+    }
+
+    #[test]
+    fn empty_input_has_no_ratio() {
+        let report = measure(&pairs(&[("", "")]), 1.5);
+        assert!(report.lines.is_empty());
+        assert_eq!(report.ratio, 0.0);
+        assert_eq!(report.over_threshold, 0);
+    }
+}
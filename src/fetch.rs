@@ -0,0 +1,242 @@
+use anyhow::{Context, Result, anyhow, bail};
+use ego_tree::NodeRef;
+use reqwest::Client;
+use reqwest::header::CONTENT_TYPE;
+use scraper::{Html, Node, Selector};
+
+/// Largest page that is downloaded for translation
+const MAX_BYTES: usize = 5 * 1024 * 1024;
+
+/// Elements that hold no readable article text
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "header", "footer", "aside", "form",
+    "button", "svg", "iframe", "head",
+];
+
+/// Elements that start a new paragraph
+const BLOCKS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "li",
+    "ul",
+    "ol",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "table",
+    "tr",
+    "dd",
+    "dt",
+    "figcaption",
+    "br",
+    "hr",
+];
+
+/// Download `url` and return its text: readable text for HTML, the body as is for
+/// plain text and Markdown
+pub async fn fetch_text(client: &Client, url: &str) -> Result<String> {
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {}", url))?;
+
+    if !response.status().is_success() {
+        bail!("Fetching {} failed with status {}", url, response.status());
+    }
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_BYTES as u64)
+    {
+        bail!("{} is larger than {} bytes", url, MAX_BYTES);
+    }
+
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(';')
+                .next()
+                .unwrap_or(value)
+                .trim()
+                .to_lowercase()
+        })
+        .unwrap_or_default();
+
+    // The length header may be missing or wrong, so cap what is actually read
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.context("Failed to read page")? {
+        if body.len() + chunk.len() > MAX_BYTES {
+            bail!("{} is larger than {} bytes", url, MAX_BYTES);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let body = String::from_utf8_lossy(&body);
+
+    match content_type.as_str() {
+        "text/html" | "application/xhtml+xml" => Ok(readable_text(&body)),
+        "" | "application/json" | "application/xml" => Ok(body.into_owned()),
+        content_type if content_type.starts_with("text/") => Ok(body.into_owned()),
+        content_type => Err(anyhow!(
+            "Cannot translate {} content from {}",
+            content_type,
+            url
+        )),
+    }
+}
+
+/// The main text of an HTML page, one paragraph per block with blank lines between.
+///
+/// Like reader modes, this prefers the `<article>` or `<main>` element and leaves out
+/// scripts, navigation, headers, footers and sidebars.
+fn readable_text(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let root = ["article", "main", "body"]
+        .iter()
+        .find_map(|tag| {
+            let selector = Selector::parse(tag).expect("Invalid selector");
+            document.select(&selector).next()
+        })
+        .map_or(document.tree.root(), |element| *element);
+
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+    collect_text(root, &mut paragraphs, &mut current);
+    end_paragraph(&mut paragraphs, &mut current);
+
+    paragraphs.join("\n\n")
+}
+
+fn collect_text(node: NodeRef<'_, Node>, paragraphs: &mut Vec<String>, current: &mut String) {
+    for child in node.children() {
+        match child.value() {
+            Node::Text(text) => current.push_str(text),
+            Node::Element(element) => {
+                let name = element.name();
+                if SKIPPED.contains(&name) {
+                    continue;
+                }
+                let block = BLOCKS.contains(&name);
+                if block {
+                    end_paragraph(paragraphs, current);
+                }
+                collect_text(child, paragraphs, current);
+                if block {
+                    end_paragraph(paragraphs, current);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Finish the paragraph being collected, collapsing its whitespace
+fn end_paragraph(paragraphs: &mut Vec<String>, current: &mut String) {
+    let paragraph = current.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph);
+    }
+    current.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn serve(content_type: &str, body: &str) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/page"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(body.as_bytes().to_vec(), content_type),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[test]
+    fn readable_text_prefers_the_article() {
+        let html = "<html><head><title>Title</title><style>p {}</style></head><body>\
+            <nav>Home | About</nav>\
+            <article><h1>Heading</h1><p>First   paragraph\n spans lines.</p>\
+            <script>track()</script><p>Second <b>bold</b> paragraph.</p></article>\
+            <footer>Copyright</footer></body></html>";
+        assert_eq!(
+            readable_text(html),
+            "Heading\n\nFirst paragraph spans lines.\n\nSecond bold paragraph."
+        );
+    }
+
+    #[test]
+    fn readable_text_falls_back_to_the_body() {
+        let html = "<body><header>Site</header><div>One</div>Two<br>Three</body>";
+        assert_eq!(readable_text(html), "One\n\nTwo\n\nThree");
+    }
+
+    #[tokio::test]
+    async fn html_is_reduced_to_its_text() {
+        let server = serve(
+            "text/html; charset=utf-8",
+            "<body><p>Hello</p><p>World</p></body>",
+        )
+        .await;
+        let text = fetch_text(&Client::new(), &format!("{}/page", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(text, "Hello\n\nWorld");
+    }
+
+    #[tokio::test]
+    async fn plain_text_is_kept_as_is() {
+        let server = serve("text/markdown", "# Title\n\n- <item>\n").await;
+        let text = fetch_text(&Client::new(), &format!("{}/page", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(text, "# Title\n\n- <item>\n");
+    }
+
+    #[tokio::test]
+    async fn binary_content_is_rejected() {
+        let server = serve("image/png", "not really a png").await;
+        let error = fetch_text(&Client::new(), &format!("{}/page", server.uri()))
+            .await
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Cannot translate image/png content"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn error_status_fails() {
+        let server = MockServer::start().await;
+        let error = fetch_text(&Client::new(), &format!("{}/missing", server.uri()))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("404"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn oversized_page_is_rejected() {
+        let server = serve("text/plain", &"a".repeat(MAX_BYTES + 1)).await;
+        let error = fetch_text(&Client::new(), &format!("{}/page", server.uri()))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("is larger than"), "{error}");
+    }
+}
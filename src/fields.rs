@@ -0,0 +1,167 @@
+/// Delimited lines such as TSV, where only some fields get translated
+pub struct Delimited<'a> {
+    separator: char,
+    rows: Vec<Row<'a>>,
+}
+
+struct Row<'a> {
+    fields: Vec<&'a str>,
+    /// Line ending after the last field, kept as it was
+    ending: &'a str,
+}
+
+impl<'a> Delimited<'a> {
+    pub fn parse(text: &'a str, separator: char) -> Self {
+        let rows = text
+            .split_inclusive('\n')
+            .map(|line| {
+                let content = line.trim_end_matches(['\r', '\n']);
+                Row {
+                    fields: content.split(separator).collect(),
+                    ending: &line[content.len()..],
+                }
+            })
+            .collect();
+
+        Self { separator, rows }
+    }
+
+    /// Text of the selected fields (1-based) in reading order, without surrounding spaces.
+    ///
+    /// Lines with fewer fields simply contribute the ones they have, and blank fields
+    /// are skipped.
+    pub fn targets(&self, columns: &[u64]) -> Vec<&'a str> {
+        self.positions(columns)
+            .map(|(row, column)| self.rows[row].fields[column].trim())
+            .collect()
+    }
+
+    /// Zero-based line and field of each field returned by `targets`
+    pub fn target_cells(&self, columns: &[u64]) -> Vec<(usize, usize)> {
+        self.positions(columns).collect()
+    }
+
+    /// Reassemble the lines with the selected fields replaced, in the order of `targets`.
+    /// A `None` translation keeps the original field.
+    pub fn render(&self, columns: &[u64], translations: Vec<Option<String>>) -> String {
+        let cells = self
+            .positions(columns)
+            .zip(translations)
+            .filter_map(|(cell, translation)| Some((cell, translation?)));
+        self.render_cells(cells)
+    }
+
+    /// Reassemble the lines with the given zero-based (line, field) cells replaced.
+    /// Cells beyond the end of their line are ignored.
+    pub fn render_cells(
+        &self,
+        cells: impl IntoIterator<Item = ((usize, usize), String)>,
+    ) -> String {
+        let mut replaced: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.fields.iter().map(|field| field.to_string()).collect())
+            .collect();
+
+        for ((row, column), translation) in cells {
+            let Some(field) = self.rows.get(row).and_then(|row| row.fields.get(column)) else {
+                continue;
+            };
+            // Keep the padding around the field, and the field on its line
+            let start = field.len() - field.trim_start().len();
+            let end = field.trim_end().len();
+            replaced[row][column] = format!(
+                "{}{}{}",
+                &field[..start],
+                translation.replace('\n', " "),
+                &field[end..]
+            );
+        }
+
+        let separator = self.separator.to_string();
+        self.rows
+            .iter()
+            .zip(replaced)
+            .map(|(row, fields)| format!("{}{}", fields.join(&separator), row.ending))
+            .collect()
+    }
+
+    fn positions(&self, columns: &[u64]) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let mut columns: Vec<usize> = columns.iter().map(|column| *column as usize - 1).collect();
+        columns.sort_unstable();
+        columns.dedup();
+
+        self.rows.iter().enumerate().flat_map(move |(index, row)| {
+            columns
+                .clone()
+                .into_iter()
+                .filter(move |column| {
+                    row.fields
+                        .get(*column)
+                        .is_some_and(|field| !field.trim().is_empty())
+                })
+                .map(move |column| (index, column))
+        })
+    }
+}
+
+/// Parse a separator given as a single character, `\t` or `tab`
+pub fn parse_separator(value: &str) -> Result<char, String> {
+    match value {
+        "\\t" | "tab" => Ok('\t'),
+        _ => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c != '\n' && c != '\r' => Ok(c),
+                _ => Err("expected a single character, `\\t` or `tab`".to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TSV: &str = "id\tsource\tnote\r\n1\t Hello \tgreeting\n2\t\tempty\n3\n";
+
+    #[test]
+    fn targets_skip_blank_and_missing_fields() {
+        let delimited = Delimited::parse(TSV, '\t');
+        assert_eq!(delimited.targets(&[2]), ["source", "Hello"]);
+        assert_eq!(delimited.target_cells(&[2]), [(0, 1), (1, 1)]);
+        assert_eq!(
+            delimited.targets(&[3, 2, 3]),
+            ["source", "note", "Hello", "greeting", "empty"]
+        );
+    }
+
+    #[test]
+    fn render_keeps_everything_but_the_translated_fields() {
+        let delimited = Delimited::parse(TSV, '\t');
+        let rendered = delimited.render(&[2], vec![None, Some("Hallo\nWelt".to_string())]);
+        assert_eq!(
+            rendered,
+            "id\tsource\tnote\r\n1\t Hallo Welt \tgreeting\n2\t\tempty\n3\n"
+        );
+    }
+
+    #[test]
+    fn cells_beyond_a_line_are_ignored() {
+        let delimited = Delimited::parse("a,b\nc", ',');
+        assert_eq!(
+            delimited.render_cells([((1, 1), "x".to_string()), ((5, 0), "y".to_string())]),
+            "a,b\nc"
+        );
+    }
+
+    #[test]
+    fn parses_separators() {
+        assert_eq!(parse_separator("tab"), Ok('\t'));
+        assert_eq!(parse_separator("\\t"), Ok('\t'));
+        assert_eq!(parse_separator(";"), Ok(';'));
+        assert!(parse_separator(",,").is_err());
+        assert!(parse_separator("").is_err());
+        assert!(parse_separator("\n").is_err());
+    }
+}
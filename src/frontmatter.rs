@@ -0,0 +1,194 @@
+/// Keys whose values are translated when --frontmatter-keys is not given
+pub const DEFAULT_KEYS: [&str; 2] = ["title", "description"];
+
+/// A leading `---` delimited YAML block, kept line by line
+pub struct Frontmatter {
+    lines: Vec<String>,
+    fields: Vec<Field>,
+}
+
+/// A top-level `key: value` line whose value is translated
+struct Field {
+    line: usize,
+    prefix: String,
+    value: String,
+    quote: Option<char>,
+}
+
+impl Frontmatter {
+    /// Split frontmatter off the start of `text`, returning it and the remaining body.
+    ///
+    /// Only single-line scalar values of the given keys are translated. Nested
+    /// values, block scalars and all other keys are kept verbatim.
+    pub fn parse<'a>(text: &'a str, keys: &[String]) -> Option<(Self, &'a str)> {
+        let rest = text.strip_prefix("---")?;
+        let rest = rest
+            .strip_prefix("\r\n")
+            .or_else(|| rest.strip_prefix('\n'))?;
+
+        let mut lines = Vec::new();
+        let mut offset = text.len() - rest.len();
+        for line in rest.split_inclusive('\n') {
+            offset += line.len();
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line == "---" || line == "..." {
+                let frontmatter = Self::from_lines(lines, keys);
+                return Some((frontmatter, &text[offset..]));
+            }
+            lines.push(line.to_string());
+        }
+
+        // Without a closing delimiter this is not frontmatter
+        None
+    }
+
+    fn from_lines(lines: Vec<String>, keys: &[String]) -> Self {
+        let fields = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                if line.starts_with([' ', '\t', '#', '-']) {
+                    return None;
+                }
+                let (key, value) = line.split_once(':')?;
+                if !keys.iter().any(|k| k == key.trim()) {
+                    return None;
+                }
+
+                let value_start = line.len() - value.trim_start().len();
+                let (value, quote) = unquote(value.trim())?;
+                Some(Field {
+                    line: index,
+                    prefix: line[..value_start].to_string(),
+                    value,
+                    quote,
+                })
+            })
+            .collect();
+
+        Self { lines, fields }
+    }
+
+    /// Values to translate, in the order `render` expects their translations
+    pub fn values(&self) -> Vec<&str> {
+        self.fields
+            .iter()
+            .map(|field| field.value.as_str())
+            .collect()
+    }
+
+    /// Re-emit the frontmatter with translated values, delimiters included
+    pub fn render(&self, translations: &[String]) -> String {
+        let mut lines = self.lines.clone();
+        for (field, translation) in self.fields.iter().zip(translations) {
+            // A translated value must stay on its own line
+            let translation = translation.replace('\n', " ");
+            lines[field.line] = format!("{}{}", field.prefix, quote(&translation, field.quote));
+        }
+
+        format!("---\n{}\n---", lines.join("\n"))
+    }
+}
+
+/// Strip YAML quotes from a scalar, or None for values that are not plain scalars
+fn unquote(value: &str) -> Option<(String, Option<char>)> {
+    if value.is_empty() || value.starts_with(['|', '>', '[', '{', '&', '*', '!']) {
+        return None;
+    }
+
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let inner = &value[1..value.len() - 1];
+        return Some((inner.replace("\\\"", "\"").replace("\\\\", "\\"), Some('"')));
+    }
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        let inner = &value[1..value.len() - 1];
+        return Some((inner.replace("''", "'"), Some('\'')));
+    }
+
+    // Drop a trailing comment from plain scalars
+    let value = match value.find(" #") {
+        Some(comment) => value[..comment].trim_end(),
+        None => value,
+    };
+    Some((value.to_string(), None))
+}
+
+/// Quote a value the same way as the original, adding quotes where YAML needs them
+fn quote(value: &str, original: Option<char>) -> String {
+    let needs_quotes = value.contains(": ")
+        || value.contains(" #")
+        || value.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c));
+
+    match original {
+        Some('\'') => format!("'{}'", value.replace('\'', "''")),
+        Some(_) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+        None if needs_quotes => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POST: &str = "---
+title: Hello world
+slug: hello-world
+date: 2024-05-01
+description: \"A first post: short\"
+tags:
+  - intro
+---
+Body text.
+";
+
+    fn keys() -> Vec<String> {
+        DEFAULT_KEYS.map(str::to_string).to_vec()
+    }
+
+    #[test]
+    fn finds_the_whitelisted_values_and_the_body() {
+        let (frontmatter, body) = Frontmatter::parse(POST, &keys()).unwrap();
+        assert_eq!(frontmatter.values(), ["Hello world", "A first post: short"]);
+        assert_eq!(body, "Body text.\n");
+    }
+
+    #[test]
+    fn renders_only_whitelisted_keys_translated() {
+        let (frontmatter, _) = Frontmatter::parse(POST, &keys()).unwrap();
+        let rendered = frontmatter.render(&["你好世界".to_string(), "第一篇: 短文".to_string()]);
+        assert_eq!(
+            rendered,
+            "---
+title: 你好世界
+slug: hello-world
+date: 2024-05-01
+description: \"第一篇: 短文\"
+tags:
+  - intro
+---"
+        );
+    }
+
+    #[test]
+    fn custom_keys() {
+        let keys = vec!["slug".to_string()];
+        let (frontmatter, _) = Frontmatter::parse(POST, &keys).unwrap();
+        assert_eq!(frontmatter.values(), ["hello-world"]);
+    }
+
+    #[test]
+    fn adds_quotes_where_the_translation_needs_them() {
+        let (frontmatter, _) = Frontmatter::parse("---\ntitle: It's\n---\n", &keys()).unwrap();
+        assert_eq!(
+            frontmatter.render(&["Note: this".to_string()]),
+            "---\ntitle: \"Note: this\"\n---"
+        );
+    }
+
+    #[test]
+    fn needs_a_closing_delimiter() {
+        assert!(Frontmatter::parse("---\ntitle: Hello\nBody\n", &keys()).is_none());
+        assert!(Frontmatter::parse("No frontmatter", &keys()).is_none());
+    }
+}
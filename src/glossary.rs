@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet};
+
+/// Common English words that never start or end a glossary term
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "because",
+    "been", "before", "but", "by", "can", "could", "do", "does", "each", "for", "from", "had",
+    "has", "have", "he", "her", "his", "how", "i", "if", "in", "into", "is", "it", "its", "just",
+    "may", "more", "most", "must", "my", "no", "not", "of", "on", "one", "only", "or", "other",
+    "our", "out", "over", "she", "should", "so", "some", "such", "than", "that", "the", "their",
+    "them", "then", "there", "these", "they", "this", "those", "to", "too", "up", "us", "use",
+    "used", "using", "very", "was", "we", "were", "what", "when", "where", "which", "while", "who",
+    "will", "with", "would", "you", "your",
+];
+
+/// Longest multi-word term considered
+const MAX_TERM_WORDS: usize = 3;
+
+/// A candidate term and how often it occurs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub text: String,
+    pub count: usize,
+}
+
+/// Find recurring multi-word terms and technical tokens worth adding to a glossary.
+///
+/// Multi-word terms must occur at least twice and may not start or end with a
+/// stopword. Technical tokens such as `camelCase`, `snake_case`, `HTTP` or `utf8`
+/// are kept even when they occur only once. Returns at most `limit` terms, most
+/// frequent first, with multi-word terms before single tokens.
+pub fn mine(text: &str, limit: usize) -> Vec<Term> {
+    let stopwords: HashSet<&str> = STOPWORDS.iter().copied().collect();
+    let mut phrases: HashMap<String, usize> = HashMap::new();
+    let mut technical: HashMap<String, usize> = HashMap::new();
+
+    for chunk in text.split(is_phrase_boundary) {
+        let words: Vec<&str> = chunk
+            .split(|c: char| !is_word_char(c))
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        for word in &words {
+            if is_technical(word) {
+                *technical.entry(word.to_string()).or_insert(0) += 1;
+            }
+        }
+
+        let lowered: Vec<String> = words.iter().map(|word| word.to_lowercase()).collect();
+        for len in 2..=MAX_TERM_WORDS {
+            for window in lowered.windows(len) {
+                let first = window[0].as_str();
+                let last = window[len - 1].as_str();
+                if stopwords.contains(first)
+                    || stopwords.contains(last)
+                    || window.iter().any(|word| is_number(word))
+                {
+                    continue;
+                }
+                *phrases.entry(window.join(" ")).or_insert(0) += 1;
+            }
+        }
+    }
+
+    phrases.retain(|_, count| *count >= 2);
+
+    // A shorter phrase that only occurs inside a longer one adds nothing
+    let subsumed: HashSet<String> = phrases
+        .keys()
+        .filter(|phrase| {
+            phrases.iter().any(|(longer, count)| {
+                longer.len() > phrase.len()
+                    && *count == phrases[*phrase]
+                    && format!(" {} ", longer).contains(&format!(" {} ", phrase))
+            })
+        })
+        .cloned()
+        .collect();
+
+    let mut terms = ranked(
+        phrases
+            .into_iter()
+            .filter(|(phrase, _)| !subsumed.contains(phrase)),
+    );
+    terms.extend(ranked(technical.into_iter()));
+    terms.truncate(limit);
+    terms
+}
+
+/// Sort by descending count, then alphabetically so the output is stable
+fn ranked(counts: impl Iterator<Item = (String, usize)>) -> Vec<Term> {
+    let mut terms: Vec<Term> = counts.map(|(text, count)| Term { text, count }).collect();
+    terms.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.text.cmp(&b.text)));
+    terms
+}
+
+/// Render terms as a TOML glossary with empty translations to fill in
+pub fn render(terms: &[Term]) -> String {
+    let mut out = String::from(
+        "# Candidate terms with their number of occurrences; fill in the translations\n[terms]\n",
+    );
+    for term in terms {
+        let key = toml::Value::String(term.text.clone());
+        out.push_str(&format!("{} = \"\" # {}\n", key, term.count));
+    }
+    out
+}
+
+/// Punctuation and line breaks that no term spans
+fn is_phrase_boundary(c: char) -> bool {
+    matches!(
+        c,
+        '\n' | '.' | ',' | ';' | ':' | '!' | '?' | '(' | ')' | '[' | ']' | '"' | '“' | '”'
+    )
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '\''
+}
+
+fn is_number(word: &str) -> bool {
+    word.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Identifiers and acronyms that are usually kept or translated consistently
+fn is_technical(word: &str) -> bool {
+    let letters = word.chars().filter(|c| c.is_alphabetic()).count();
+    if letters == 0 {
+        return false;
+    }
+
+    let acronym = letters >= 2 && word.chars().all(|c| c.is_uppercase() || c.is_ascii_digit());
+    let camel_case =
+        word.chars().skip(1).any(char::is_uppercase) && word.chars().any(char::is_lowercase);
+    let mixed_digits = word.chars().any(|c| c.is_ascii_digit());
+    acronym || camel_case || mixed_digits || word.contains('_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(terms: &[Term]) -> Vec<&str> {
+        terms.iter().map(|term| term.text.as_str()).collect()
+    }
+
+    #[test]
+    fn finds_recurring_phrases_without_stopword_edges() {
+        let text = "The access token expires. Refresh the access token daily.\n\
+                    An access token is required for the API.";
+        let terms = mine(text, 10);
+        assert_eq!(
+            terms[0],
+            Term {
+                text: "access token".to_string(),
+                count: 3
+            }
+        );
+        assert!(!texts(&terms).contains(&"the access token"));
+        assert!(!texts(&terms).contains(&"access token expires"));
+    }
+
+    #[test]
+    fn shorter_phrase_inside_a_longer_one_is_dropped() {
+        let terms = mine("rate limit error. rate limit error.", 10);
+        assert_eq!(texts(&terms), ["rate limit error"]);
+    }
+
+    #[test]
+    fn phrases_do_not_span_punctuation() {
+        assert!(
+            mine("end point. end point. end, point. end, point.", 10)
+                .iter()
+                .all(|term| term.text == "end point" && term.count == 2)
+        );
+    }
+
+    #[test]
+    fn technical_tokens_are_kept_even_once() {
+        let terms = mine(
+            "Call getValue with max_retries over HTTP using utf8 and plain words",
+            10,
+        );
+        assert_eq!(texts(&terms), ["HTTP", "getValue", "max_retries", "utf8"]);
+    }
+
+    #[test]
+    fn limits_the_number_of_terms() {
+        assert_eq!(mine("HTTP TCP UDP", 2).len(), 2);
+    }
+
+    #[test]
+    fn renders_a_toml_table_to_fill_in() {
+        let terms = [
+            Term {
+                text: "access token".to_string(),
+                count: 3,
+            },
+            Term {
+                text: "say \"hi\"".to_string(),
+                count: 2,
+            },
+        ];
+        let rendered = render(&terms);
+        assert!(
+            rendered.ends_with("[terms]\n\"access token\" = \"\" # 3\n'say \"hi\"' = \"\" # 2\n"),
+            "{}",
+            rendered
+        );
+        let parsed: toml::Value = toml::from_str(&rendered).unwrap();
+        assert_eq!(parsed["terms"]["say \"hi\""].as_str(), Some(""));
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+
+use crate::tokens;
+
+/// Estimated tokens of earlier turns sent along with each request
+const TOKEN_BUDGET: usize = 1000;
+
+/// A request sent in an earlier turn and the translation it produced
+#[derive(Debug, Clone)]
+pub struct Turn {
+    pub prompt: String,
+    pub translation: String,
+}
+
+/// The most recent turns of an interactive session, replayed as conversation context
+#[derive(Debug, Clone)]
+pub struct History {
+    turns: VecDeque<Turn>,
+    max_turns: usize,
+}
+
+impl History {
+    pub fn new(max_turns: usize) -> Self {
+        Self {
+            turns: VecDeque::new(),
+            max_turns,
+        }
+    }
+
+    /// Record a turn, dropping the oldest ones beyond the turn limit or token budget
+    pub fn push(&mut self, prompt: &str, translation: &str) {
+        self.turns.push_back(Turn {
+            prompt: prompt.to_string(),
+            translation: translation.to_string(),
+        });
+
+        while self.turns.len() > self.max_turns || self.tokens() > TOKEN_BUDGET {
+            if self.turns.pop_front().is_none() {
+                break;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.turns.clear();
+    }
+
+    pub fn turns(&self) -> impl Iterator<Item = &Turn> {
+        self.turns.iter()
+    }
+
+    fn tokens(&self) -> usize {
+        self.turns
+            .iter()
+            .map(|turn| {
+                tokens::estimate_tokens(&turn.prompt) + tokens::estimate_tokens(&turn.translation)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompts(history: &History) -> Vec<&str> {
+        history.turns().map(|turn| turn.prompt.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_the_most_recent_turns() {
+        let mut history = History::new(2);
+        history.push("one", "eins");
+        history.push("two", "zwei");
+        history.push("three", "drei");
+        assert_eq!(prompts(&history), ["two", "three"]);
+
+        history.clear();
+        assert_eq!(history.turns().count(), 0);
+    }
+
+    #[test]
+    fn drops_turns_beyond_the_token_budget() {
+        let mut history = History::new(10);
+        // About four characters make a token
+        let long = "x".repeat(TOKEN_BUDGET * 8);
+        history.push(&long, "short");
+        history.push("two", "zwei");
+        history.push("three", "drei");
+        // A turn over the budget on its own is not kept either
+        assert_eq!(prompts(&history), ["two", "three"]);
+    }
+}
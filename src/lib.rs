@@ -0,0 +1,24 @@
+//! Translation through OpenAI-compatible chat endpoints and DeepL, as used by the
+//! `tzh` command line tool.
+//!
+//! Build a [`translator::Translator`] from a [`config::Config`] and translate single
+//! lines, batches with [`translator::Translator::translate_batch`] or streams of
+//! lines with [`translator::Translator::translate_stream`].
+
+pub mod audit;
+pub mod backend;
+pub mod card;
+pub mod clock;
+pub mod config;
+pub mod debug_bundle;
+pub mod deepl;
+pub mod history;
+pub mod output;
+pub mod placeholders;
+pub mod preset;
+pub mod segmentation;
+pub mod sse;
+pub mod text;
+pub mod theme;
+pub mod tokens;
+pub mod translator;
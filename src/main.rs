@@ -1,19 +1,67 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result, bail};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
-use std::io::{self, BufRead, BufReader, Read, Write};
+use futures::stream::{self, StreamExt};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-mod config;
-mod translator;
+mod comments;
+mod error_report;
+mod expansion;
+mod fetch;
+mod fields;
+mod frontmatter;
+mod glossary;
+mod markdown;
+mod score;
+mod stats;
+mod tui;
+mod watch;
 
-use config::Config;
-use translator::Translator;
+// The translation library, also reachable as `crate::` from the modules above
+use tzh::{
+    audit, backend, card, config, debug_bundle, output, preset, segmentation, text, theme, tokens,
+    translator,
+};
+
+use audit::AuditLog;
+use card::WordCard;
+use comments::{CodeLanguage, Piece};
+use config::{BackendKind, Config};
+use debug_bundle::DebugBundle;
+use error_report::{ErrorReport, FailedRow};
+use fields::Delimited;
+use frontmatter::Frontmatter;
+use output::{LineEnding, Output};
+use preset::Preset;
+use segmentation::Segmenter;
+use stats::SessionStats;
+use theme::{Theme, Themed};
+use translator::{CostLimitReached, ProviderSpec, Translation, Translator};
 
 #[derive(Parser)]
 #[command(name = "tzh")]
 #[command(about = "AI-powered translation tool")]
 #[command(version = "0.1.0")]
 struct Cli {
+    /// Print extra diagnostics (e.g. fallback usage) to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Disable colored output
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Log the full text of each request to the configured audit log instead of only its hash
+    #[arg(long, global = true)]
+    audit_full: bool,
+    /// Use the prompts and temperature of a preset from the presets directory (see `tzh presets list`)
+    #[arg(long, global = true, value_name = "NAME")]
+    preset: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -22,35 +70,76 @@ struct Cli {
 enum Commands {
     /// Translate text
     #[command(alias = "t")]
-    Translate {
-        /// Text to translate (multiple words will be joined with spaces). If no text is provided, reads from stdin.
-        text: Vec<String>,
+    Translate(Box<TranslateArgs>),
+    /// Interactive translation mode (translate each line as you type)
+    #[command(alias = "i")]
+    Interactive {
         /// Target language (e.g., zh, en, ja, ko, fr, de, es)
         #[arg(short, long, default_value = "zh")]
         to: String,
         /// Source language (auto-detect if not specified)
         #[arg(short, long)]
         from: Option<String>,
-        /// Plain output (only show translation result, no formatting)
-        #[arg(short, long)]
-        plain: bool,
-        /// Translate line by line for streaming output
-        #[arg(short, long)]
-        stream: bool,
+        /// Use the simple line-based prompt instead of the split-pane view
+        #[arg(long)]
+        simple: bool,
+        /// Reload the config before each line if the config file has changed
+        #[arg(long)]
+        watch_config: bool,
+        /// Don't add bidi control characters around right-to-left translations
+        #[arg(long)]
+        no_bidi: bool,
+        /// Send the last N turns along with each line for context (`/clear` resets them)
+        #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "5")]
+        with_history: Option<usize>,
+        /// Don't print the session summary (translations, tokens, cost, latency) on exit
+        #[arg(long)]
+        no_summary: bool,
     },
-    /// Interactive translation mode (translate each line as you type)
-    #[command(alias = "i")]
-    Interactive {
+    /// Watch a file and re-translate it into an output file whenever it changes
+    #[command(alias = "w")]
+    Watch {
+        /// File to watch
+        file: PathBuf,
+        /// File the translation is written to
+        #[arg(short, long)]
+        output: PathBuf,
         /// Target language (e.g., zh, en, ja, ko, fr, de, es)
         #[arg(short, long, default_value = "zh")]
         to: String,
         /// Source language (auto-detect if not specified)
         #[arg(short, long)]
         from: Option<String>,
+        /// Number of lines translated concurrently
+        #[arg(short = 'j', long, default_value_t = 1)]
+        concurrency: usize,
+    },
+    /// Score a translated file against a reference translation with chrF and BLEU
+    Score {
+        /// Translated file, one segment per line
+        candidate: PathBuf,
+        /// Reference translation with the same number of lines
+        reference: PathBuf,
+        /// Print the scores as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Build and maintain glossaries
+    Glossary {
+        #[command(subcommand)]
+        command: GlossaryCommand,
+    },
+    /// Manage prompt presets
+    Presets {
+        #[command(subcommand)]
+        command: PresetsCommand,
     },
     /// Configure the translator
     #[command(alias = "c")]
     Config {
+        /// Set translation backend
+        #[arg(long, value_enum)]
+        backend: Option<BackendKind>,
         /// Set API endpoint
         #[arg(long)]
         endpoint: Option<String>,
@@ -66,122 +155,1334 @@ enum Commands {
         /// Set max tokens (None for unlimited)
         #[arg(long)]
         max_tokens: Option<i32>,
+        /// Enable or disable prompt caching markers for Anthropic and OpenRouter (true/false)
+        #[arg(long)]
+        prompt_caching: Option<bool>,
+        /// Replace the output colors with a built-in scheme
+        #[arg(long, value_enum)]
+        theme: Option<Theme>,
     },
     /// Show current configuration
     #[command(alias = "s")]
     Status,
 }
 
+/// Options of `tzh translate`
+#[derive(Args)]
+struct TranslateArgs {
+    /// Text to translate (multiple words will be joined with spaces). If no text is provided, reads from stdin.
+    text: Vec<String>,
+    /// Read the text to translate from a file instead of stdin
+    #[arg(long, conflicts_with = "text")]
+    file: Option<PathBuf>,
+    /// Fetch the text to translate from a URL, keeping only the readable text of HTML pages
+    #[arg(long, conflicts_with_all = ["text", "file"])]
+    url: Option<String>,
+    /// Write the output to this file instead of stdout
+    #[arg(short, long = "output", value_name = "PATH")]
+    output_file: Option<PathBuf>,
+    /// Target language (e.g., zh, en, ja, ko, fr, de, es)
+    #[arg(short, long, default_value = "zh")]
+    to: String,
+    /// Source language (auto-detect if not specified)
+    #[arg(short, long)]
+    from: Option<String>,
+    /// Keep format placeholders (`{0}`, `%s`, `%1$d`, `{{name}}`) exactly as they are
+    #[arg(long)]
+    protect_placeholders: bool,
+    /// Likely source language, given to the model as a hint it may override (unlike --from)
+    #[arg(long, value_name = "LANG", conflicts_with = "from")]
+    source_hint: Option<String>,
+    /// Plain output (only show translation result, no formatting)
+    #[arg(short, long)]
+    plain: bool,
+    /// Translate line by line for streaming output
+    #[arg(short, long)]
+    stream: bool,
+    /// Merge runs of blank lines into a single blank line in stream mode
+    #[arg(long, requires = "stream")]
+    collapse_blanks: bool,
+    /// Compact output (one `word → translation` line per input line)
+    #[arg(short, long, conflicts_with = "plain")]
+    compact: bool,
+    /// Keep translating the remaining lines when one line fails
+    #[arg(short, long)]
+    keep_going: bool,
+    /// Abort once this many consecutive lines have failed (with --keep-going)
+    #[arg(long, requires = "keep_going", value_parser = clap::value_parser!(u64).range(1..))]
+    max_line_failures: Option<u64>,
+    /// Write the lines that failed, with their text and error, to this CSV file
+    #[arg(long, value_name = "PATH")]
+    error_report: Option<PathBuf>,
+    /// Translate the rows of an --error-report again and merge them into the input,
+    /// which is the output of the run that wrote the report
+    #[arg(long, value_name = "REPORT", group = "field_rows", conflicts_with_all = ["code_comments", "consensus"])]
+    retry_errors: Option<PathBuf>,
+    /// Output emitted in place of a failed line when using --keep-going
+    #[arg(long, value_enum, default_value = "error")]
+    placeholder: Placeholder,
+    /// Also translate lines and words that are only a number, URL or code identifier
+    #[arg(long)]
+    translate_all: bool,
+    /// Show a confidence score computed from token logprobs (if the endpoint supports it)
+    #[arg(long)]
+    with_confidence: bool,
+    /// Line ending of the output
+    #[arg(long, value_enum, default_value = "auto")]
+    line_ending: LineEnding,
+    /// Leave out the line ending after the last line of output, as `$(...)` would
+    #[arg(long)]
+    no_final_newline: bool,
+    /// Number of lines translated concurrently in stream mode
+    #[arg(short = 'j', long, default_value_t = 1)]
+    concurrency: usize,
+    /// Override the configured max tokens for this run
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..), conflicts_with = "unlimited")]
+    max_tokens: Option<i32>,
+    /// Send no max tokens limit for this run
+    #[arg(long)]
+    unlimited: bool,
+    /// Estimate max tokens for each request from its input length and languages
+    #[arg(long, conflicts_with_all = ["max_tokens", "unlimited"])]
+    auto_tokens: bool,
+    /// Join hard-wrapped lines into whole sentences before translating
+    #[arg(long)]
+    unwrap: bool,
+    /// Re-wrap translations to the width of the original text (with --unwrap)
+    #[arg(long, requires = "unwrap")]
+    rewrap: bool,
+    /// Translate only the comments of source code, leaving the code untouched
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "auto")]
+    code_comments: Option<CodeLanguage>,
+    /// Don't add bidi control characters around right-to-left translations
+    #[arg(long)]
+    no_bidi: bool,
+    /// Print the effective settings of this run to stderr as one `key=value` line
+    #[arg(long)]
+    echo_config: bool,
+    /// Only estimate the size and cost of the job, without translating anything
+    #[arg(long)]
+    count_only: bool,
+    /// Treat the input as Markdown and translate the values of its frontmatter (on for `.md` files)
+    #[arg(long)]
+    markdown: bool,
+    /// Frontmatter keys whose values are translated, comma separated [default: title,description]
+    #[arg(long, value_delimiter = ',')]
+    frontmatter_keys: Option<Vec<String>>,
+    /// How sentence boundaries are found for --unwrap and --count-only
+    #[arg(long, value_enum, default_value = "simple")]
+    segmenter: Segmenter,
+    /// Show formatted output in `$PAGER` when writing to a terminal
+    #[arg(long)]
+    pager: bool,
+    /// Type out stream mode output at this many milliseconds per character on a terminal
+    #[arg(long, value_name = "MS", requires = "stream", conflicts_with = "pager")]
+    typewriter: Option<u64>,
+    /// Keep indentation and list markers (`-`, `*`, `+`, `1.`, `1)`) of each line as they are (on for Markdown)
+    #[arg(long)]
+    preserve_lists: bool,
+    /// Split each line into fields on this character (`\t` or `tab` for a tab)
+    #[arg(long, value_name = "CHAR", value_parser = fields::parse_separator, requires = "field_rows", conflicts_with = "code_comments")]
+    input_separator: Option<char>,
+    /// Fields to translate with --input-separator, counted from 1 and comma separated
+    #[arg(long, value_name = "INDICES", value_delimiter = ',', group = "field_rows", requires = "input_separator", value_parser = clap::value_parser!(u64).range(1..))]
+    translate_fields: Option<Vec<u64>>,
+    /// Translate Markdown as plain prose, dropping formatting, link URLs and code blocks
+    #[arg(long, conflicts_with = "code_comments")]
+    strip_markdown: bool,
+    /// Show the reasoning of reasoning models under each translation
+    #[arg(long, conflicts_with_all = ["plain", "compact"])]
+    show_reasoning: bool,
+    /// Report on stderr how much longer or shorter each translated line is than its original
+    #[arg(long, group = "json_report", conflicts_with_all = ["code_comments", "input_separator", "retry_errors", "consensus"])]
+    measure_expansion: bool,
+    /// Flag lines whose translation is longer than this percentage of the original
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = 150.0,
+        requires = "measure_expansion"
+    )]
+    expansion_threshold: f64,
+    /// Look words up as cards with a definition in their own language besides the translation
+    #[arg(long, group = "json_report", conflicts_with_all = ["stream", "code_comments", "input_separator", "retry_errors", "consensus"])]
+    bidirectional: bool,
+    /// Print the expansion report or word cards as JSON
+    #[arg(long, requires = "json_report")]
+    json: bool,
+    /// Translate with several providers at once, given as comma separated `endpoint:model` specs
+    #[arg(long, value_name = "SPECS", value_delimiter = ',', value_parser = ProviderSpec::parse, conflicts_with_all = ["stream", "compact", "code_comments", "input_separator"])]
+    consensus: Option<Vec<ProviderSpec>>,
+    /// Let the configured chat model pick the most accurate of the consensus candidates
+    #[arg(long, requires = "consensus")]
+    judge: bool,
+    /// Refuse to start if the estimated cost is above this, and stop once it is reached
+    #[arg(long, value_name = "AMOUNT")]
+    max_cost: Option<f64>,
+    /// Run even if the estimated cost is above the maximum cost, still stopping once it is reached
+    #[arg(long, alias = "force")]
+    yes: bool,
+    /// Write the config, requests, responses and timings of this run to a JSON file, with secrets removed
+    #[arg(long, value_name = "PATH")]
+    debug_bundle: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum GlossaryCommand {
+    /// Collect frequent terms and technical tokens from a file into a glossary to fill in
+    Mine {
+        /// Source text to mine
+        file: PathBuf,
+        /// Write the glossary to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Maximum number of terms
+        #[arg(short = 'n', long, default_value_t = 50)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum PresetsCommand {
+    /// List the presets in the presets directory
+    List,
+}
+
+/// What to print in place of a line that failed under --keep-going
+#[derive(Clone, Copy, ValueEnum)]
+enum Placeholder {
+    /// An empty line
+    Blank,
+    /// The untranslated source line
+    Original,
+    /// The literal text `[ERROR]`
+    Error,
+}
+
+impl Placeholder {
+    fn render<'a>(&self, original: &'a str) -> &'a str {
+        match self {
+            Placeholder::Blank => "",
+            Placeholder::Original => original,
+            Placeholder::Error => "[ERROR]",
+        }
+    }
+}
+
+/// Tracks failed lines under --keep-going and trips the --max-line-failures breaker
+struct LineFailures {
+    keep_going: bool,
+    report: Option<ErrorReport>,
+    max_consecutive: Option<u64>,
+    total: u64,
+    consecutive: u64,
+    completed: u64,
+}
+
+impl LineFailures {
+    fn new(keep_going: bool, max_consecutive: Option<u64>, report: Option<ErrorReport>) -> Self {
+        Self {
+            keep_going,
+            report,
+            max_consecutive,
+            total: 0,
+            consecutive: 0,
+            completed: 0,
+        }
+    }
+
+    fn succeeded(&mut self) {
+        self.completed += 1;
+        self.consecutive = 0;
+    }
+
+    /// Record a failed line, or a failed field of it, exiting unless --keep-going allows continuing
+    fn failed(&mut self, index: usize, field: Option<usize>, source: &str, error: &anyhow::Error) {
+        if let Some(report) = &mut self.report {
+            let row = FailedRow {
+                line: index + 1,
+                field: field.map(|field| field as u64 + 1),
+                source: source.to_string(),
+                error: format!("{:#}", error),
+            };
+            if let Err(e) = report.record(&row) {
+                output::stderr_line(format!("Failed to write error report: {}", e));
+                output::exit(1);
+            }
+        }
+
+        if !self.keep_going || error.is::<CostLimitReached>() {
+            output::stderr_line(format!("Translation failed: {}", error));
+            output::exit(1);
+        }
+
+        let location = match field {
+            Some(field) => format!("Line {} field {} failed:", index + 1, field + 1),
+            None => format!("Line {} failed:", index + 1),
+        };
+        output::stderr_line(format!("{} {}", location.error(), error));
+        self.total += 1;
+        self.consecutive += 1;
+
+        if let Some(max) = self.max_consecutive
+            && self.consecutive >= max
+        {
+            output::stderr_line(
+                format!(
+                    "{} consecutive lines failed, aborting after {} completed line(s)",
+                    self.consecutive, self.completed
+                )
+                .error(),
+            );
+            output::exit(1);
+        }
+    }
+
+    /// Exit with an error if any line failed
+    fn finish(&self) {
+        if self.total > 0 {
+            output::stderr_line(format!("{} line(s) failed to translate", self.total).error());
+            output::exit(1);
+        }
+    }
+}
+
+/// Rebuilds the translator when the config file changes during an interactive session
+struct ConfigReloader {
+    verbose: bool,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigReloader {
+    fn new(verbose: bool) -> Self {
+        Self {
+            verbose,
+            modified: Config::modified(),
+        }
+    }
+
+    /// Returns a translator built from the new config if the file changed since the last check.
+    /// The session history and audit log of `current` carry over to it.
+    fn reload(&mut self, current: &Translator) -> Option<Result<Translator>> {
+        if Config::modified() == self.modified {
+            return None;
+        }
+
+        let result = Config::load().map(|config| {
+            let mut translator = Translator::new(&config);
+            translator.set_verbose(self.verbose);
+            translator.inherit_session(current);
+            translator
+        });
+
+        // Loading may add missing fields to the file, so remember the time after that
+        self.modified = Config::modified();
+        Some(result)
+    }
+}
+
+/// Translate the comments in `source`, returning the code with only comments changed
+async fn translate_code_comments(
+    translator: &Translator,
+    source: &str,
+    language: CodeLanguage,
+    to: &str,
+    from: Option<&str>,
+    concurrency: usize,
+    failures: &mut LineFailures,
+) -> String {
+    let mut pieces = comments::split(source, language);
+    let texts: Vec<&str> = pieces
+        .iter()
+        .filter_map(|piece| match piece {
+            Piece::Comment { text, .. } => Some(text.as_str()),
+            Piece::Code(_) => None,
+        })
+        .collect();
+
+    let results = translator
+        .translate_batch(&texts, to, from, concurrency)
+        .await;
+
+    let comments = pieces.iter_mut().filter_map(|piece| match piece {
+        Piece::Comment { text, line } => Some((text, *line)),
+        Piece::Code(_) => None,
+    });
+    for ((text, line), result) in comments.zip(results) {
+        match result {
+            // A line comment must stay on one line
+            Ok(translation) => *text = translation.text.replace('\n', " "),
+            Err(e) => failures.failed(line, None, text, &e),
+        }
+    }
+
+    comments::join(&pieces)
+}
+
+/// Translate the selected fields of delimited lines, keeping all other fields as they are
+async fn translate_delimited_fields(
+    translator: &Translator,
+    delimited: &Delimited<'_>,
+    columns: &[u64],
+    to: &str,
+    from: Option<&str>,
+    concurrency: usize,
+    failures: &mut LineFailures,
+) -> String {
+    let targets = delimited.targets(columns);
+    let results = translator
+        .translate_batch(&targets, to, from, concurrency)
+        .await;
+
+    let translations = results
+        .into_iter()
+        .zip(delimited.target_cells(columns))
+        .zip(targets)
+        .map(|((result, (line, field)), source)| match result {
+            Ok(translation) => Some(translation.text),
+            Err(e) => {
+                failures.failed(line, Some(field), source, &e);
+                None
+            }
+        })
+        .collect();
+
+    delimited.render(columns, translations)
+}
+
+/// Translate the rows of an error report again and put them into `text`, the output of
+/// the run that wrote the report. Rows that fail again keep what `text` has.
+#[allow(clippy::too_many_arguments)]
+async fn retry_failed_rows(
+    translator: &Translator,
+    text: &str,
+    rows: &[FailedRow],
+    separator: Option<char>,
+    to: &str,
+    from: Option<&str>,
+    concurrency: usize,
+    failures: &mut LineFailures,
+) -> Result<String> {
+    if rows.iter().any(|row| row.line == 0 || row.field == Some(0)) {
+        bail!("Error report line and field numbers start at 1");
+    }
+    if separator.is_none() && rows.iter().any(|row| row.field.is_some()) {
+        bail!("The error report lists fields, pass the --input-separator of the original run");
+    }
+
+    let sources: Vec<&str> = rows.iter().map(|row| row.source.as_str()).collect();
+    let results = translator
+        .translate_batch(&sources, to, from, concurrency)
+        .await;
+
+    let mut lines = HashMap::new();
+    let mut cells = Vec::new();
+    for (row, result) in rows.iter().zip(results) {
+        let field = row.field.map(|field| field as usize - 1);
+        match (result, field) {
+            (Ok(translation), Some(field)) => cells.push(((row.line - 1, field), translation.text)),
+            (Ok(translation), None) => {
+                lines.insert(row.line - 1, translation.text);
+            }
+            (Err(e), _) => failures.failed(row.line - 1, field, &row.source, &e),
+        }
+    }
+
+    let text = match separator {
+        Some(separator) => Delimited::parse(text, separator).render_cells(cells),
+        None => text.to_string(),
+    };
+    Ok(text
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(index, line)| match lines.get(&index) {
+            Some(translation) => {
+                let content = line.trim_end_matches(['\r', '\n']);
+                format!(
+                    "{}{}",
+                    translation.replace('\n', " "),
+                    &line[content.len()..]
+                )
+            }
+            None => line.to_string(),
+        })
+        .collect())
+}
+
+/// Print a word card stacked, on one line in compact mode, as tab separated
+/// word, translation and definition in plain mode, or as a JSON line
+fn print_card(
+    output: &Output,
+    card: &WordCard,
+    to: &str,
+    plain: bool,
+    compact: bool,
+    json: bool,
+) -> Result<()> {
+    let definition = card.definition.as_deref().unwrap_or_default();
+    if json {
+        output.line(serde_json::to_string(card)?);
+    } else if plain {
+        output.line(format!(
+            "{}\t{}\t{}",
+            card.word, card.translation, definition
+        ));
+    } else if compact {
+        let mut line = format!(
+            "{} {} {}",
+            card.word,
+            "→".dimmed(),
+            card.translation.translation()
+        );
+        if !definition.is_empty() {
+            line.push_str(&format!("  {}", definition.dimmed()));
+        }
+        output.line(line);
+    } else {
+        output.blank();
+        output.line("Word:".header().bold());
+        output.line(&card.word);
+        output.line(format!("Translation ({}):", to).header().bold());
+        output.line(card.translation.translation());
+        if let Some(definition) = &card.definition {
+            output.line("Definition:".header().bold());
+            output.line(definition);
+        }
+    }
+    Ok(())
+}
+
+/// Print an expansion report to stderr, which keeps the translation on stdout clean
+fn print_expansion(report: &expansion::Report, json: bool) -> Result<()> {
+    if json {
+        eprintln!("{}", serde_json::to_string_pretty(report)?);
+        return Ok(());
+    }
+
+    for line in &report.lines {
+        let ratio = format!("{:6.1}%", line.ratio * 100.0);
+        eprintln!(
+            "{:>5}  {:>5} → {:>5} {}",
+            line.line,
+            line.original_chars,
+            line.translation_chars,
+            if line.over_threshold {
+                ratio.error()
+            } else {
+                ratio.normal()
+            }
+        );
+    }
+    eprintln!(
+        "{} {} → {} chars ({:.1}%), {} line(s) over {:.0}%",
+        "Expansion:".header().bold(),
+        report.original_chars,
+        report.translation_chars,
+        report.ratio * 100.0,
+        report.over_threshold,
+        report.threshold * 100.0
+    );
+    Ok(())
+}
+
+/// Options of a --consensus run
+struct Consensus<'a> {
+    providers: &'a [ProviderSpec],
+    judge: bool,
+    plain: bool,
+}
+
+impl Consensus<'_> {
+    /// Show the candidate of every provider labelled with its provider, marking the
+    /// judged best one. Plain output has only the judged best candidate if there is one.
+    async fn run(
+        &self,
+        translator: &Translator,
+        output: &Output,
+        text: &str,
+        to: &str,
+        from: Option<&str>,
+    ) {
+        if !self.plain {
+            output.line(format!("Translating with {} providers...", self.providers.len()).status());
+        }
+
+        let results = translator
+            .translate_consensus(text, self.providers, to, from)
+            .await;
+        let candidates: Vec<(usize, &str)> = results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, result)| {
+                let translation = result.as_ref().ok()?;
+                Some((index, translation.text.as_str()))
+            })
+            .collect();
+        if candidates.is_empty() {
+            eprintln!("{}", "All consensus providers failed".error());
+        }
+
+        let choice = if self.judge && !candidates.is_empty() {
+            let texts: Vec<&str> = candidates.iter().map(|(_, text)| *text).collect();
+            match translator.judge(text, &texts, to, from).await {
+                Ok(choice) => Some(candidates[choice].0),
+                Err(e) => {
+                    eprintln!("{} {}", "Judging failed:".error(), e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        for (index, (spec, result)) in self.providers.iter().zip(&results).enumerate() {
+            if self.plain {
+                match (choice, result) {
+                    (Some(choice), Ok(translation)) if choice == index => {
+                        output.line(&translation.text)
+                    }
+                    (None, Ok(translation)) => {
+                        output.line(format!("[{}] {}", spec.label(), translation.text))
+                    }
+                    (None, Err(e)) => eprintln!("[{}] failed: {}", spec.label(), e),
+                    _ => {}
+                }
+                continue;
+            }
+
+            let selected = if choice == Some(index) {
+                " (selected)"
+            } else {
+                ""
+            };
+            output.blank();
+            output.line(
+                format!("Candidate {} from {}{}:", index + 1, spec.label(), selected)
+                    .header()
+                    .bold(),
+            );
+            match result {
+                Ok(translation) => output.line(translation.text.translation()),
+                Err(e) => output.line(format!("Failed: {}", e).error()),
+            }
+        }
+
+        if candidates.is_empty() || (self.judge && choice.is_none()) {
+            output::exit(1);
+        }
+    }
+}
+
+/// Print the size and estimated cost of translating `segments`, for --count-only
+fn print_estimate(
+    output: &Output,
+    config: &Config,
+    segments: &[&str],
+    to: &str,
+    from: Option<&str>,
+    translate_all: bool,
+    segmenter: Segmenter,
+) {
+    let sentences: usize = segments
+        .iter()
+        .map(|segment| segmentation::segment_sentences(segment, from, segmenter).len())
+        .sum();
+    let estimate = tokens::estimate_job(segments, from, to, |segment| {
+        !translate_all && translator::is_untranslatable(segment)
+    });
+
+    output.line(format!("Segments: {}", estimate.segments));
+    output.line(format!("Sentences: {}", sentences));
+    output.line(format!("Passed through: {}", estimate.passthrough));
+    output.line(format!("Requests: {}", estimate.requests));
+    output.line(format!("Estimated input tokens: {}", estimate.input_tokens));
+    output.line(format!(
+        "Estimated output tokens: {}",
+        estimate.output_tokens
+    ));
+    if let Some(pricing) = config.pricing() {
+        output.line(format!(
+            "Estimated cost: {:.4}",
+            pricing.cost(estimate.input_tokens, estimate.output_tokens)
+        ));
+    }
+}
+
+/// Refuse to start a run whose estimated cost is above the maximum cost per run
+fn check_cost(
+    config: &Config,
+    segments: &[&str],
+    to: &str,
+    from: Option<&str>,
+    translate_all: bool,
+) -> Result<()> {
+    let (Some(max_cost), Some(pricing)) = (config.max_cost_per_run(), config.pricing()) else {
+        return Ok(());
+    };
+
+    let estimate = tokens::estimate_job(segments, from, to, |segment| {
+        !translate_all && translator::is_untranslatable(segment)
+    });
+    let cost = pricing.cost(estimate.input_tokens, estimate.output_tokens);
+    if cost > max_cost {
+        anyhow::bail!(
+            "Estimated cost {:.4} is above the maximum of {:.4} per run (pass --yes to run anyway)",
+            cost,
+            max_cost
+        );
+    }
+    Ok(())
+}
+
+/// Trim an input line, keeping its indentation if list structure is preserved
+fn trim_line(line: &str, keep_indent: bool) -> &str {
+    if keep_indent {
+        line.trim_end()
+    } else {
+        line.trim()
+    }
+}
+
 fn has_blank(text: &str) -> bool {
     text.as_bytes().iter().any(|&b| b.is_ascii_whitespace())
 }
 
+/// Create a translator for a command, logging to the audit log if one is configured
+fn new_translator(
+    config: &Config,
+    verbose: bool,
+    audit_full: bool,
+    preset: Option<&Preset>,
+) -> Result<Translator> {
+    let mut translator = Translator::new(config);
+    translator.set_verbose(verbose);
+    translator.set_preset(preset.cloned());
+    if let Some(path) = config.audit_log() {
+        translator.set_audit_log(Arc::new(AuditLog::open(path, audit_full)?));
+    }
+    Ok(translator)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
     let mut config = Config::load()?;
 
+    if cli.no_color {
+        colored::control::set_override(false);
+    }
+    theme::init(config.colors());
+    let preset = cli.preset.as_deref().map(Preset::load).transpose()?;
+
     match cli.command {
-        Commands::Translate {
-            text,
-            to,
-            from,
-            plain,
-            stream,
-        } => {
-            let translator = Translator::new(&config);
+        Commands::Translate(args) => {
+            let TranslateArgs {
+                text,
+                file,
+                url,
+                output_file,
+                to,
+                from,
+                source_hint,
+                protect_placeholders,
+                plain,
+                stream,
+                collapse_blanks,
+                compact,
+                keep_going,
+                max_line_failures,
+                error_report,
+                retry_errors,
+                placeholder,
+                translate_all,
+                with_confidence,
+                line_ending,
+                no_final_newline,
+                concurrency,
+                max_tokens,
+                unlimited,
+                auto_tokens,
+                unwrap,
+                rewrap,
+                code_comments,
+                no_bidi,
+                echo_config,
+                count_only,
+                preserve_lists,
+                markdown,
+                frontmatter_keys,
+                pager,
+                typewriter,
+                segmenter,
+                input_separator,
+                translate_fields,
+                strip_markdown,
+                show_reasoning,
+                measure_expansion,
+                expansion_threshold,
+                bidirectional,
+                json,
+                consensus,
+                judge,
+                max_cost,
+                yes,
+                debug_bundle,
+            } = *args;
+            let markdown = markdown || file.as_deref().is_some_and(markdown::is_markdown_file);
+            let preserve_lists = preserve_lists || markdown;
+            // The judge is asked in a chat, which DeepL can't answer
+            if judge && config.backend() == BackendKind::Deepl {
+                bail!("--judge needs the chat backend, but the configured backend is DeepL");
+            }
+            // Per-run overrides are applied to the loaded config but never saved
+            if unlimited {
+                config.set_max_tokens(None);
+            } else if let Some(max_tokens) = max_tokens {
+                config.set_max_tokens(Some(max_tokens));
+            }
+            if max_cost.is_some() {
+                config.set_max_cost_per_run(max_cost);
+            }
 
-            // Get the text to translate either from arguments or stdin
-            let input_text = if text.is_empty() {
+            let mut translator =
+                new_translator(&config, cli.verbose, cli.audit_full, preset.as_ref())?;
+            translator.set_translate_all(translate_all);
+            translator.set_with_confidence(with_confidence);
+            translator.set_auto_max_tokens(auto_tokens);
+            translator.set_preserve_lists(preserve_lists);
+            translator.set_source_hint(source_hint);
+            translator.set_protect_placeholders(protect_placeholders);
+            translator.set_bidirectional(bidirectional);
+            if let Some(max_cost) = config.max_cost_per_run() {
+                let pricing = config
+                    .pricing()
+                    .context("A maximum cost needs a [pricing] table in the config")?;
+                translator.set_max_cost(pricing.clone(), max_cost);
+            }
+            if let Some(path) = debug_bundle {
+                translator.set_debug_bundle(Arc::new(DebugBundle::create(path, &config)?));
+            }
+
+            if echo_config {
+                eprintln!(
+                    "tzh-config: {} auto_tokens={} to={} from={}",
+                    config.summary(),
+                    auto_tokens,
+                    to,
+                    from.as_deref().unwrap_or("auto")
+                );
+            }
+
+            // Get the text to translate either from arguments, a file, a URL or stdin
+            let raw_text = if let Some(url) = &url {
+                fetch::fetch_text(translator.client(), url).await?
+            } else if let Some(path) = &file {
+                fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?
+            } else if text.is_empty() {
                 // Read from stdin if no text arguments provided
                 let mut buffer = String::new();
                 io::stdin().read_to_string(&mut buffer)?;
-                buffer.trim().to_string()
+                buffer
             } else {
                 // Join all text arguments with spaces
                 text.join(" ")
             };
 
-            if input_text.is_empty() {
-                eprintln!("{}", "No text provided to translate".red());
+            // Colors only make sense on a terminal
+            if let Some(path) = &output_file {
+                output::write_to_file(path)?;
+                colored::control::set_override(false);
+            }
+
+            let report = error_report
+                .as_deref()
+                .map(ErrorReport::create)
+                .transpose()?;
+            let mut failures = LineFailures::new(keep_going, max_line_failures, report);
+
+            // The input already is a translation, only the reported rows change
+            if let Some(path) = &retry_errors {
+                let rows = error_report::read(path)?;
+                let sources: Vec<&str> = rows.iter().map(|row| row.source.as_str()).collect();
+                let output = Output::new("\n", !no_final_newline);
+                if count_only {
+                    print_estimate(
+                        &output,
+                        &config,
+                        &sources,
+                        &to,
+                        from.as_deref(),
+                        translate_all,
+                        segmenter,
+                    );
+                    return Ok(());
+                }
+                if !yes {
+                    check_cost(&config, &sources, &to, from.as_deref(), translate_all)?;
+                }
+
+                let merged = retry_failed_rows(
+                    &translator,
+                    &raw_text,
+                    &rows,
+                    input_separator,
+                    &to,
+                    from.as_deref(),
+                    concurrency,
+                    &mut failures,
+                )
+                .await?;
+
+                output.raw(&merged);
+                failures.finish();
                 return Ok(());
             }
 
-            if !plain {
-                println!("{}", "Translating...".blue());
+            // Code is passed through byte for byte, so skip all text preprocessing
+            if let Some(language) = code_comments {
+                let language = language
+                    .resolve(file.as_deref())
+                    .context("Cannot infer the code language, pass it to --code-comments")?;
+                let pieces = comments::split(&raw_text, language);
+                let segments: Vec<&str> = pieces
+                    .iter()
+                    .filter_map(|piece| match piece {
+                        Piece::Comment { text, .. } => Some(text.as_str()),
+                        Piece::Code(_) => None,
+                    })
+                    .collect();
+                // The text keeps its own line endings
+                let output = Output::new("\n", !no_final_newline);
+                if count_only {
+                    print_estimate(
+                        &output,
+                        &config,
+                        &segments,
+                        &to,
+                        from.as_deref(),
+                        translate_all,
+                        segmenter,
+                    );
+                    return Ok(());
+                }
+                if !yes {
+                    check_cost(&config, &segments, &to, from.as_deref(), translate_all)?;
+                }
+
+                let translated = translate_code_comments(
+                    &translator,
+                    &raw_text,
+                    language,
+                    &to,
+                    from.as_deref(),
+                    concurrency,
+                    &mut failures,
+                )
+                .await;
+
+                output.raw(&translated);
+                failures.finish();
+                return Ok(());
             }
 
+            // Only the selected fields are sent, everything else is kept byte for byte
+            if let (Some(separator), Some(columns)) = (input_separator, &translate_fields) {
+                let delimited = Delimited::parse(&raw_text, separator);
+                let segments = delimited.targets(columns);
+                // The text keeps its own line endings
+                let output = Output::new("\n", !no_final_newline);
+                if count_only {
+                    print_estimate(
+                        &output,
+                        &config,
+                        &segments,
+                        &to,
+                        from.as_deref(),
+                        translate_all,
+                        segmenter,
+                    );
+                    return Ok(());
+                }
+                if !yes {
+                    check_cost(&config, &segments, &to, from.as_deref(), translate_all)?;
+                }
+
+                let translated = translate_delimited_fields(
+                    &translator,
+                    &delimited,
+                    columns,
+                    &to,
+                    from.as_deref(),
+                    concurrency,
+                    &mut failures,
+                )
+                .await;
+
+                output.raw(&translated);
+                failures.finish();
+                return Ok(());
+            }
+
+            // Work with LF internally and restore the requested line ending on output
+            let output = Output::new(
+                line_ending.resolve(text::detect_line_ending(&raw_text)),
+                !no_final_newline,
+            );
+            let normalized = text::normalize_newlines(&raw_text);
+
+            // Frontmatter values are translated on their own, the body as usual. Other
+            // text may well start with a `---` line, so only Markdown is searched for it.
+            let find_frontmatter = markdown || frontmatter_keys.is_some();
+            let frontmatter_keys = frontmatter_keys
+                .unwrap_or_else(|| frontmatter::DEFAULT_KEYS.map(str::to_string).to_vec());
+            let stripped;
+            let (frontmatter, body) = if strip_markdown {
+                // Frontmatter is not prose, so it goes along with the formatting
+                stripped = markdown::to_plain_text(&normalized);
+                (None, stripped.as_str())
+            } else if find_frontmatter
+                && let Some((frontmatter, body)) =
+                    Frontmatter::parse(&normalized, &frontmatter_keys)
+            {
+                (Some(frontmatter), body)
+            } else {
+                (None, normalized.as_str())
+            };
+
+            // Stream mode reproduces leading blank lines, so only trim the end there.
+            // Kept list structure includes the indentation of the first line.
+            let mut input_text = if stream {
+                body.trim_end().to_string()
+            } else if preserve_lists {
+                body.trim_start_matches('\n').trim_end().to_string()
+            } else {
+                body.trim().to_string()
+            };
+
+            // Remember the original width before joining wrapped lines
+            let wrap_width = rewrap.then(|| text::max_line_width(&input_text));
+            // Controls are a rendering hint for the terminal, files and pipes get the text as is
+            let bidi = !no_bidi
+                && !json
+                && config.bidi_control()
+                && text::is_rtl_language(&to)
+                && output_file.is_none()
+                && io::stdout().is_terminal();
+            if unwrap {
+                input_text = text::unwrap_lines(&input_text, from.as_deref(), segmenter).join("\n");
+            }
+
+            if input_text.trim().is_empty() {
+                eprintln!("{}", "No text provided to translate".error());
+                return Ok(());
+            }
+
+            // Each line is a request of its own in stream and compact modes
+            let segments: Vec<&str> = if stream || compact {
+                input_text.lines().map(|line| line.trim()).collect()
+            } else {
+                vec![input_text.trim()]
+            };
+            if count_only {
+                print_estimate(
+                    &output,
+                    &config,
+                    &segments,
+                    &to,
+                    from.as_deref(),
+                    translate_all,
+                    segmenter,
+                );
+                return Ok(());
+            }
+            if !yes {
+                check_cost(&config, &segments, &to, from.as_deref(), translate_all)?;
+            }
+
+            if let Some(mut providers) = consensus {
+                for provider in &mut providers {
+                    provider.api_key = config.api_key_for(&provider.endpoint).map(str::to_string);
+                }
+                let consensus = Consensus {
+                    providers: &providers,
+                    judge,
+                    plain,
+                };
+                consensus
+                    .run(
+                        &translator,
+                        &output,
+                        input_text.trim(),
+                        &to,
+                        from.as_deref(),
+                    )
+                    .await;
+                return Ok(());
+            }
+
+            // Plain output is meant for scripts, so it is never paged
+            if (pager || config.pager())
+                && !plain
+                && output_file.is_none()
+                && io::stdout().is_terminal()
+            {
+                output::start_pager()?;
+            }
+            // Pacing is for people watching, so scripts and files get the text right away
+            if let Some(delay) = typewriter
+                && !plain
+                && !json
+                && output_file.is_none()
+                && io::stdout().is_terminal()
+            {
+                output::start_typewriter(Duration::from_millis(delay));
+            }
+
+            if !plain && !compact && !json {
+                output.line("Translating...".status());
+            }
+
+            if let Some(frontmatter) = &frontmatter {
+                let translations = translator
+                    .translate_batch(&frontmatter.values(), &to, from.as_deref(), concurrency)
+                    .await
+                    .into_iter()
+                    .map(|result| result.map(|translation| translation.text))
+                    .collect::<Result<Vec<_>>>();
+                match translations {
+                    Ok(translations) => output.line(frontmatter.render(&translations)),
+                    Err(e) => {
+                        eprintln!("Failed to translate frontmatter: {}", e);
+                        output::exit(1);
+                    }
+                }
+            }
+
+            // Lengths are measured before wrapping and bidi controls change the text
+            let measured = RefCell::new(Vec::new());
+
             // Create callback for translation results
-            let callback = |original: &str, translation: &str| {
-                if translation.is_empty() {
-                    println!(); // Empty line
+            let callback = |original: &str, translation: &Translation| {
+                if measure_expansion {
+                    measured
+                        .borrow_mut()
+                        .push((original.to_string(), translation.text.clone()));
+                }
+
+                if translation.text.is_empty() {
+                    output.blank(); // Empty line
                     return;
                 }
 
+                let display;
+                let translation = if wrap_width.is_some() || bidi {
+                    let mut text = translation.text.clone();
+                    if let Some(width) = wrap_width {
+                        text = text::wrap_to_width(&text, width);
+                    }
+                    if bidi {
+                        text = text::embed_rtl(&text);
+                    }
+                    display = Translation {
+                        text,
+                        ..translation.clone()
+                    };
+                    &display
+                } else {
+                    translation
+                };
+
+                let confidence = match translation.confidence {
+                    Some(confidence) if !plain => {
+                        format!(" ({:.1}% confidence)", confidence * 100.0)
+                    }
+                    _ => String::new(),
+                };
+
                 if plain {
                     // Plain mode: just output the translation
-                    println!("{}", translation);
+                    output.line(&translation.text);
+                } else if compact {
+                    // Compact mode: source and translation on a single line
+                    output.line(format!(
+                        "{} {} {}{}",
+                        original,
+                        "→".dimmed(),
+                        translation.text.translation(),
+                        confidence.dimmed()
+                    ));
                 } else {
-                    println!(); // Add separator between lines
-                    println!("{}", "Original:".green().bold());
-                    println!("{}", original);
-                    println!("{}", format!("Translation ({}):", to).green().bold());
-                    println!("{}", translation.bright_white());
+                    output.blank(); // Add separator between lines
+                    output.line("Original:".header().bold());
+                    output.line(original);
+                    output.line(format!("Translation ({}):", to).header().bold());
+                    output.line(format!(
+                        "{}{}",
+                        translation.text.translation(),
+                        confidence.dimmed()
+                    ));
+                    if show_reasoning && let Some(reasoning) = &translation.reasoning {
+                        let header = match translation.reasoning_tokens {
+                            Some(tokens) => format!("Reasoning ({} tokens):", tokens),
+                            None => "Reasoning:".to_string(),
+                        };
+                        output.line(header.header().bold());
+                        output.line(reasoning.dimmed());
+                    }
                 }
             };
+            // Compact mode looks up every input line on its own
+            if compact {
+                for (index, line) in input_text
+                    .lines()
+                    .map(|line| trim_line(line, preserve_lists))
+                    .enumerate()
+                {
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    // Phrases are looked up as cards too, so JSON Lines stay JSON
+                    let result = if bidirectional {
+                        translator
+                            .translate_card(line, &to, from.as_deref())
+                            .await
+                            .and_then(|card| print_card(&output, &card, &to, plain, compact, json))
+                    } else if has_blank(line) {
+                        translator
+                            .translate_line(line, &to, from.as_deref(), &callback)
+                            .await
+                    } else {
+                        translator
+                            .translate_word(line, &to, from.as_deref(), &callback)
+                            .await
+                    };
 
-            // Check whether is a word or phrase
-            if has_blank(&input_text) {
+                    match result {
+                        Ok(()) => failures.succeeded(),
+                        Err(e) => {
+                            failures.failed(index, None, line, &e);
+                            if !(bidirectional && json) {
+                                callback(line, &Translation::new(placeholder.render(line)));
+                            }
+                        }
+                    }
+                }
+            } else if has_blank(input_text.trim()) && !bidirectional {
+                // Check whether is a word or phrase
                 // Split input text into lines if streaming
-                let lines: Vec<&str> = if stream {
-                    input_text.lines().map(|line| line.trim()).collect()
+                let mut lines: Vec<&str> = if stream {
+                    input_text
+                        .lines()
+                        .map(|line| trim_line(line, preserve_lists))
+                        .collect()
                 } else {
-                    vec![input_text.trim()]
+                    vec![trim_line(&input_text, preserve_lists)]
                 };
 
-                for line in lines {
-                    // Translate each line
-                    match translator
-                        .translate_line(line, &to, from.as_deref(), &callback)
-                        .await
-                    {
-                        Ok(()) => { /* Nothing to do, because callback has done everything */ }
+                // Each blank source line yields one blank output line unless collapsing
+                if collapse_blanks {
+                    lines.dedup_by(|line, previous| line.is_empty() && previous.is_empty());
+                }
+
+                // Print each line as soon as it and all lines before it are done
+                let lines = stream::iter(lines.into_iter().map(str::to_string));
+                let results = translator.translate_stream(lines, &to, from.as_deref(), concurrency);
+                let mut results = pin!(results.enumerate());
+                while let Some((index, (line, result))) = results.next().await {
+                    match result {
+                        Ok(translation) => {
+                            callback(&line, &translation);
+                            failures.succeeded();
+                        }
                         Err(e) => {
-                            eprintln!("Translation failed: {}", e);
-                            std::process::exit(1);
+                            failures.failed(index, None, &line, &e);
+                            callback(&line, &Translation::new(placeholder.render(&line)));
                         }
                     }
                 }
             } else {
-                // Translate single word
-                match translator
-                    .translate_word(&input_text, &to, from.as_deref(), callback)
-                    .await
-                {
+                // Translate single word, or look up a word or phrase as a card
+                let word = input_text.trim();
+                let result = if bidirectional {
+                    translator
+                        .translate_card(word, &to, from.as_deref())
+                        .await
+                        .and_then(|card| print_card(&output, &card, &to, plain, compact, json))
+                } else {
+                    translator
+                        .translate_word(word, &to, from.as_deref(), callback)
+                        .await
+                };
+                match result {
                     Ok(()) => { /* Nothing to do, because callback has done everything */ }
                     Err(e) => {
                         eprintln!("Translation failed: {}", e);
-                        std::process::exit(1);
+                        output::exit(1);
                     }
                 }
             }
+
+            output::finish_typewriter();
+            output::finish_pager();
+            if measure_expansion {
+                let report =
+                    expansion::measure(&measured.into_inner(), expansion_threshold / 100.0);
+                print_expansion(&report, json)?;
+            }
+            failures.finish();
         }
-        Commands::Interactive { to, from } => {
-            let translator = Translator::new(&config);
+        Commands::Interactive {
+            to,
+            from,
+            simple,
+            watch_config,
+            no_bidi,
+            with_history,
+            no_summary,
+        } => {
+            let mut translator =
+                new_translator(&config, cli.verbose, cli.audit_full, preset.as_ref())?;
+            if let Some(turns) = with_history {
+                translator.set_history(turns);
+            }
+            let mut reloader = watch_config.then(|| ConfigReloader::new(cli.verbose));
+            let mut stats = SessionStats::default();
+            // Controls are a rendering hint for the terminal, pipes get the text as is
+            let bidi_control = !no_bidi && config.bidi_control() && io::stdout().is_terminal();
+
+            // The split-pane view needs a real terminal on both ends
+            if !simple && io::stdin().is_terminal() && io::stdout().is_terminal() {
+                tui::run(
+                    translator,
+                    &to,
+                    from.as_deref(),
+                    reloader.as_mut(),
+                    &mut stats,
+                    bidi_control,
+                )
+                .await?;
+                if !no_summary {
+                    stats.print_summary(config.pricing());
+                }
+                return Ok(());
+            }
 
             println!(
                 "{}",
-                "Interactive translation mode (Ctrl+C to exit)"
-                    .blue()
+                "Interactive translation mode (Ctrl+D to exit)"
+                    .title()
                     .bold()
             );
-            println!("{} {}", "Target language:".green(), to);
+            println!("{} {}", "Target language:".label(), to);
+            let bidi = bidi_control && text::is_rtl_language(&to);
             if let Some(ref from_lang) = from {
-                println!("{} {}", "Source language:".green(), from_lang);
+                println!("{} {}", "Source language:".label(), from_lang);
             } else {
-                println!("{}", "Source language: auto-detect".green());
+                println!("{}", "Source language: auto-detect".label());
             }
-            print!("{} ", "tzh>".green().bold());
+            print!("{} ", "tzh>".prompt().bold());
             io::stdout().flush().unwrap();
 
             let stdin = io::stdin();
@@ -200,23 +1501,52 @@ async fn main() -> Result<()> {
 
                 // Skip empty lines
                 if text.is_empty() {
-                    print!("{} ", "tzh>".green().bold());
+                    print!("{} ", "tzh>".prompt().bold());
+                    io::stdout().flush().unwrap();
+                    continue;
+                }
+
+                if text == "/clear" {
+                    translator.clear_history();
+                    println!("{}", "History cleared".dimmed());
+                    print!("{} ", "tzh>".prompt().bold());
                     io::stdout().flush().unwrap();
                     continue;
                 }
 
+                if let Some(result) = reloader
+                    .as_mut()
+                    .and_then(|reloader| reloader.reload(&translator))
+                {
+                    match result {
+                        Ok(reloaded) => {
+                            translator = reloaded;
+                            println!("{}", "Config reloaded".dimmed());
+                        }
+                        Err(e) => eprintln!("{} {}", "Failed to reload config:".error(), e),
+                    }
+                }
+
                 // Create callback for translation results
-                let callback = |_original: &str, translation: &str| {
-                    if translation.is_empty() {
+                let mut translated = None;
+                let callback = |_original: &str, translation: &Translation| {
+                    translated = Some(translation.clone());
+                    if translation.text.is_empty() {
                         return;
                     }
 
-                    println!("{}", translation.bright_white());
+                    let text = if bidi {
+                        text::embed_rtl(&translation.text)
+                    } else {
+                        translation.text.clone()
+                    };
+                    println!("{}", text.translation());
                 };
 
-                println!("{}", "Translating...".blue());
+                println!("{}", "Translating...".status());
 
                 // Translate the input
+                let started = Instant::now();
                 let result = if has_blank(text) {
                     translator
                         .translate_line(text, &to, from.as_deref(), callback)
@@ -227,51 +1557,168 @@ async fn main() -> Result<()> {
                         .await
                 };
 
-                if let Err(e) = result {
-                    eprintln!("{} {}", "Translation failed:".red(), e);
+                match (result, translated) {
+                    (Ok(()), Some(translation)) => {
+                        stats.record(text, &translation, started.elapsed())
+                    }
+                    (Ok(()), None) => {}
+                    (Err(e), _) => {
+                        eprintln!("{} {}", "Translation failed:".error(), e);
+                        stats.record_failure();
+                    }
                 }
 
                 // Show prompt for next input
-                print!("{} ", "tzh>".green().bold());
+                print!("{} ", "tzh>".prompt().bold());
                 io::stdout().flush().unwrap();
             }
+
+            // The prompt is still showing after end of input
+            println!();
+            if !no_summary {
+                stats.print_summary(config.pricing());
+            }
+        }
+        Commands::Watch {
+            file,
+            output,
+            to,
+            from,
+            concurrency,
+        } => {
+            let translator = new_translator(&config, cli.verbose, cli.audit_full, preset.as_ref())?;
+
+            watch::FileWatcher::new(&translator, &to, from.as_deref(), concurrency)
+                .run(&file, &output)
+                .await?;
+        }
+        Commands::Score {
+            candidate,
+            reference,
+            json,
+        } => {
+            let candidate = fs::read_to_string(&candidate)
+                .with_context(|| format!("Failed to read {}", candidate.display()))?;
+            let reference = fs::read_to_string(&reference)
+                .with_context(|| format!("Failed to read {}", reference.display()))?;
+            let candidates: Vec<&str> = candidate.lines().map(str::trim).collect();
+            let references: Vec<&str> = reference.lines().map(str::trim).collect();
+            if candidates.len() != references.len() {
+                anyhow::bail!(
+                    "Candidate has {} lines but reference has {}",
+                    candidates.len(),
+                    references.len()
+                );
+            }
+
+            let report = score::score(&candidates, &references);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                for line in &report.lines {
+                    println!(
+                        "{:>5}  chrF {:6.2}  BLEU {:6.2}",
+                        line.line, line.chrf, line.bleu
+                    );
+                }
+                println!(
+                    "{} chrF {:.2}  BLEU {:.2}",
+                    "Corpus:".header().bold(),
+                    report.chrf,
+                    report.bleu
+                );
+            }
+        }
+        Commands::Glossary {
+            command:
+                GlossaryCommand::Mine {
+                    file,
+                    output,
+                    limit,
+                },
+        } => {
+            let source = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let glossary = glossary::render(&glossary::mine(&source, limit));
+
+            match output {
+                Some(path) => {
+                    fs::write(&path, glossary)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    println!("{} {}", "Glossary written to".status(), path.display());
+                }
+                None => output::write_stdout(&glossary),
+            }
+        }
+        Commands::Presets {
+            command: PresetsCommand::List,
+        } => {
+            let presets = Preset::list()?;
+            if presets.is_empty() {
+                println!("{} {}", "No presets in".status(), Preset::dir()?.display());
+            }
+            for preset in presets {
+                match &preset.description {
+                    Some(description) => println!("{}  {}", preset.name, description.dimmed()),
+                    None => println!("{}", preset.name),
+                }
+            }
         }
         Commands::Config {
+            backend,
             endpoint,
             api_key,
             model,
             temperature,
             max_tokens,
+            prompt_caching,
+            theme,
         } => {
+            if let Some(backend) = backend {
+                config.set_backend(backend);
+                println!("{} {:?}", "Backend set to:".label(), backend);
+            }
+
             if let Some(endpoint) = endpoint {
                 config.set_endpoint(&endpoint);
-                println!("{} {}", "Endpoint set to:".green(), endpoint);
+                println!("{} {}", "Endpoint set to:".label(), endpoint);
             }
 
             if let Some(model) = model {
                 config.set_model(&model);
-                println!("{} {}", "Model set to:".green(), model);
+                println!("{} {}", "Model set to:".label(), model);
             }
 
             if let Some(api_key) = api_key {
                 config.set_api_key(&api_key);
-                println!("{}", "API key updated".green());
+                println!("{}", "API key updated".label());
             }
 
             if let Some(temperature) = temperature {
                 config.set_temperature(temperature);
-                println!("{} {}", "Temperature set to:".green(), temperature);
+                println!("{} {}", "Temperature set to:".label(), temperature);
             }
 
             if let Some(max_tokens) = max_tokens {
                 config.set_max_tokens(Some(max_tokens));
-                println!("{} {}", "Max tokens set to:".green(), max_tokens);
+                println!("{} {}", "Max tokens set to:".label(), max_tokens);
+            }
+
+            if let Some(prompt_caching) = prompt_caching {
+                config.set_prompt_caching(prompt_caching);
+                println!("{} {}", "Prompt caching set to:".label(), prompt_caching);
+            }
+
+            if let Some(theme) = theme {
+                config.set_theme(theme);
+                println!("{} {:?}", "Theme set to:".label(), theme);
             }
 
             config.save()?;
         }
         Commands::Status => {
-            println!("{}", "Current Configuration:".blue().bold());
+            println!("{}", "Current Configuration:".title().bold());
+            println!("Backend: {:?}", config.backend());
             println!("Endpoint: {}", config.endpoint());
             println!("Model: {}", config.model());
             println!("Temperature: {}", config.temperature());
@@ -282,12 +1729,16 @@ async fn main() -> Result<()> {
                     .map(|t| t.to_string())
                     .unwrap_or_else(|| "Unlimited".to_string())
             );
+            println!("Prompt caching: {}", config.prompt_caching());
+            if let Some(fallback) = config.fallback() {
+                println!("Fallback: {} ({})", fallback.endpoint, fallback.model);
+            }
             println!(
                 "API key: {}",
                 if config.has_api_key() {
-                    "Set".green()
+                    "Set".label()
                 } else {
-                    "Not set".red()
+                    "Not set".error()
                 }
             );
         }
@@ -295,3 +1746,46 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Pricing;
+
+    fn priced_config(max_cost: f64) -> Config {
+        let mut config = Config::default();
+        config.pricing = Some(Pricing {
+            input: 1_000_000.0,
+            output: 1_000_000.0,
+        });
+        config.set_max_cost_per_run(Some(max_cost));
+        config
+    }
+
+    #[test]
+    fn cost_check_aborts_above_the_cap() {
+        let config = priced_config(1.0);
+        let error = check_cost(&config, &["Hello world"], "zh", None, false).unwrap_err();
+        assert!(error.to_string().contains("--yes"), "{}", error);
+    }
+
+    #[test]
+    fn cost_check_proceeds_under_the_cap() {
+        let config = priced_config(1_000_000.0);
+        check_cost(&config, &["Hello world"], "zh", None, false).unwrap();
+    }
+
+    #[test]
+    fn cost_check_needs_pricing_and_a_cap() {
+        let mut config = priced_config(1.0);
+        config.pricing = None;
+        check_cost(&config, &["Hello world"], "zh", None, false).unwrap();
+        check_cost(&Config::default(), &["Hello world"], "zh", None, false).unwrap();
+    }
+
+    #[test]
+    fn untranslatable_segments_cost_nothing() {
+        let config = priced_config(1.0);
+        check_cost(&config, &["3.14", "https://x.com"], "zh", None, false).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use std::path::Path;
+
+/// Extensions of files read as Markdown
+const EXTENSIONS: [&str; 4] = ["md", "markdown", "mdx", "mkd"];
+
+/// Whether the file name says the file is Markdown
+pub fn is_markdown_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            EXTENSIONS
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Reduce Markdown to plain prose, one paragraph, heading or list item per block.
+///
+/// Formatting characters and link URLs are dropped while link and image text is
+/// kept. Code blocks, HTML and frontmatter are left out entirely, inline code is
+/// kept as text. Blocks are separated by a blank line.
+pub fn to_plain_text(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+
+    let mut blocks = Vec::new();
+    let mut current = String::new();
+    // Depth of blocks whose contents are left out
+    let mut skipped = 0;
+
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::MetadataBlock(_)) => {
+                skipped += 1;
+            }
+            Event::End(TagEnd::CodeBlock | TagEnd::HtmlBlock | TagEnd::MetadataBlock(_)) => {
+                skipped -= 1;
+            }
+            _ if skipped > 0 => {}
+            // A nested list starts a new block after the text of its parent item
+            Event::Start(Tag::Item | Tag::List(_)) => flush(&mut blocks, &mut current),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::Item
+                | TagEnd::TableHead
+                | TagEnd::TableRow
+                | TagEnd::FootnoteDefinition,
+            ) => flush(&mut blocks, &mut current),
+            Event::End(TagEnd::TableCell) => current.push(' '),
+            Event::Text(text) | Event::Code(text) => current.push_str(&text),
+            Event::SoftBreak => current.push(' '),
+            Event::HardBreak => current.push('\n'),
+            _ => {}
+        }
+    }
+    flush(&mut blocks, &mut current);
+
+    blocks.join("\n\n")
+}
+
+fn flush(blocks: &mut Vec<String>, current: &mut String) {
+    let block = current.trim();
+    if !block.is_empty() {
+        blocks.push(block.to_string());
+    }
+    current.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_markdown_extensions() {
+        assert!(is_markdown_file(Path::new("docs/post.md")));
+        assert!(is_markdown_file(Path::new("README.MD")));
+        assert!(is_markdown_file(Path::new("page.mdx")));
+        assert!(!is_markdown_file(Path::new("notes.txt")));
+        assert!(!is_markdown_file(Path::new("md")));
+    }
+
+    #[test]
+    fn drops_formatting_and_link_urls() {
+        let markdown =
+            "# Title\n\nSome **bold** and *em* text with a [link](https://x.com)\nand `code`.";
+        assert_eq!(
+            to_plain_text(markdown),
+            "Title\n\nSome bold and em text with a link and code."
+        );
+    }
+
+    #[test]
+    fn leaves_out_code_blocks_html_and_frontmatter() {
+        let markdown = "---\ntitle: Post\n---\n\nIntro\n\n```rust\nfn main() {}\n```\n\n<div>html</div>\n\nEnd";
+        assert_eq!(to_plain_text(markdown), "Intro\n\nEnd");
+    }
+
+    #[test]
+    fn list_items_and_table_rows_are_blocks() {
+        let markdown = "- one\n  - nested\n- two\n\n| a | b |\n|---|---|\n| c | d |";
+        assert_eq!(
+            to_plain_text(markdown),
+            "one\n\nnested\n\ntwo\n\na b\n\nc d"
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::cell::Cell;
+use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+use std::process::{self, Child, ChildStdin, Command, Stdio};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{self as tokio_mpsc, UnboundedReceiver, UnboundedSender};
+
+use crate::audit;
+use crate::clock::{Sleeper, TokioSleeper};
+use crate::theme::Themed;
+
+/// Exit status of a process killed by SIGPIPE, as reported by shells
+const BROKEN_PIPE_STATUS: i32 = 128 + 13;
+
+/// Line ending written after each line of output
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LineEnding {
+    /// Unix style `\n`
+    Lf,
+    /// Windows style `\r\n`
+    Crlf,
+    /// Match the line ending of the input
+    Auto,
+}
+
+impl LineEnding {
+    /// Resolve to the actual line ending, using `detected` for `auto`
+    pub fn resolve(self, detected: &'static str) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Auto => detected,
+        }
+    }
+}
+
+/// Writes translation output to stdout with a configurable line ending.
+///
+/// Every line ends with the line ending, the last one included, unless the final
+/// newline is turned off. Then each line ending is held back until the next line
+/// is printed, so the last one is never written.
+pub struct Output {
+    line_ending: &'static str,
+    final_newline: bool,
+    /// A line ending is owed before the next line
+    pending: Cell<bool>,
+}
+
+impl Output {
+    pub fn new(line_ending: &'static str, final_newline: bool) -> Self {
+        Self {
+            line_ending,
+            final_newline,
+            pending: Cell::new(false),
+        }
+    }
+
+    /// Print a line followed by the configured line ending
+    pub fn line(&self, text: impl Display) {
+        let text = text.to_string();
+
+        // Multi-line translations must use the same line ending throughout
+        let text = if self.line_ending == "\n" {
+            text
+        } else {
+            text.replace('\n', self.line_ending)
+        };
+        let pending = if self.pending.replace(false) {
+            self.line_ending
+        } else {
+            ""
+        };
+
+        if self.final_newline {
+            write_stdout(&format!("{}{}{}", pending, text, self.line_ending));
+        } else {
+            write_stdout(&format!("{}{}", pending, text));
+            self.pending.set(true);
+        }
+    }
+
+    /// Print an empty line
+    pub fn blank(&self) {
+        self.line("");
+    }
+
+    /// Print text that carries its own line endings, such as a file passed through
+    /// byte for byte, dropping its final line ending if the final newline is off
+    pub fn raw(&self, text: &str) {
+        let text = match text.strip_suffix('\n') {
+            Some(stripped) if !self.final_newline => {
+                stripped.strip_suffix('\r').unwrap_or(stripped)
+            }
+            _ => text,
+        };
+        write_stdout(text);
+    }
+}
+
+/// Pager process that receives all output once started
+struct Pager {
+    child: Child,
+    stdin: ChildStdin,
+}
+
+static PAGER: Mutex<Option<Pager>> = Mutex::new(None);
+
+/// File that receives all output instead of stdout, once created
+static OUTPUT_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Send all further output to a new file at `path` instead of stdout
+pub fn write_to_file(path: &Path) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    *OUTPUT_FILE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Output queued for the typewriter, which keeps stderr in order with stdout
+enum Typed {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// Thread that types out all output at a steady pace once started
+struct Typewriter {
+    sender: UnboundedSender<Typed>,
+    thread: JoinHandle<()>,
+}
+
+static TYPEWRITER: Mutex<Option<Typewriter>> = Mutex::new(None);
+
+/// Show all further output one character per `delay`.
+///
+/// Text is queued for a background thread, so translation goes on at full speed
+/// while earlier lines are still being typed. Color codes are written without delay.
+pub fn start_typewriter(delay: Duration) {
+    let (sender, receiver) = tokio_mpsc::unbounded_channel();
+    // The thread waits on the timer of the running runtime
+    let runtime = Handle::current();
+    let thread = thread::spawn(move || {
+        let (mut stdout, mut stderr) = (io::stdout(), io::stderr());
+        let typed = type_out(receiver, &mut stdout, &mut stderr, &TokioSleeper, delay);
+        if let Err(e) = runtime.block_on(typed) {
+            // Not `exit`, which would wait for this very thread
+            if e.kind() == ErrorKind::BrokenPipe {
+                process::exit(BROKEN_PIPE_STATUS);
+            }
+            eprintln!("Failed to write output: {}", e);
+            process::exit(1);
+        }
+    });
+
+    *TYPEWRITER.lock().unwrap() = Some(Typewriter { sender, thread });
+}
+
+/// Write queued output one character at a time, waiting `delay` after each one
+async fn type_out(
+    mut queue: UnboundedReceiver<Typed>,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+    sleeper: &dyn Sleeper,
+    delay: Duration,
+) -> io::Result<()> {
+    let mut in_escape = false;
+    while let Some(typed) = queue.recv().await {
+        let text = match typed {
+            Typed::Stdout(text) => text,
+            Typed::Stderr(text) => {
+                stderr.write_all(text.as_bytes())?;
+                stderr.flush()?;
+                continue;
+            }
+        };
+
+        for c in text.chars() {
+            let mut buffer = [0; 4];
+            stdout.write_all(c.encode_utf8(&mut buffer).as_bytes())?;
+            stdout.flush()?;
+
+            if c == '\x1b' {
+                in_escape = true;
+            } else if in_escape {
+                in_escape = !c.is_ascii_alphabetic();
+            } else {
+                sleeper.sleep(delay).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Wait until the typewriter has shown all queued output
+pub fn finish_typewriter() {
+    let typewriter = TYPEWRITER.lock().unwrap().take();
+    if let Some(Typewriter { sender, thread }) = typewriter {
+        drop(sender);
+        let _ = thread.join();
+    }
+}
+
+/// Print a line to stderr, after any output the typewriter has yet to type
+pub fn stderr_line(text: impl Display) {
+    if let Some(typewriter) = TYPEWRITER.lock().unwrap().as_ref() {
+        let _ = typewriter.sender.send(Typed::Stderr(format!("{}\n", text)));
+        return;
+    }
+    eprintln!("{}", text);
+}
+
+/// Receiver of notices while the interactive TUI owns the terminal
+static NOTICES: Mutex<Option<UnboundedSender<String>>> = Mutex::new(None);
+
+/// Show a progress message such as a retry on stderr, or in the status line of the
+/// interactive TUI while it runs
+pub fn notice(message: &str) {
+    if let Some(sender) = NOTICES.lock().unwrap().as_ref() {
+        let _ = sender.send(message.to_string());
+        return;
+    }
+    stderr_line(message.status());
+}
+
+/// Send all further notices to the returned receiver instead of stderr
+pub fn capture_notices() -> UnboundedReceiver<String> {
+    let (sender, receiver) = tokio_mpsc::unbounded_channel();
+    *NOTICES.lock().unwrap() = Some(sender);
+    receiver
+}
+
+/// Print notices to stderr again
+pub fn release_notices() {
+    NOTICES.lock().unwrap().take();
+}
+
+/// Send all further output through `$PAGER`, or `less -R` to keep colors
+pub fn start_pager() -> Result<()> {
+    let command = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less -R".to_string());
+
+    // Like git, let less quit right away when the output fits on one screen
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .env(
+            "LESS",
+            env::var("LESS").unwrap_or_else(|_| "FRX".to_string()),
+        )
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start pager {:?}", command))?;
+    let stdin = child.stdin.take().context("Failed to open pager input")?;
+
+    *PAGER.lock().unwrap() = Some(Pager { child, stdin });
+    Ok(())
+}
+
+/// Close the pager's input and wait until the user quits it
+pub fn finish_pager() {
+    if let Some(Pager { mut child, stdin }) = PAGER.lock().unwrap().take() {
+        drop(stdin);
+        let _ = child.wait();
+    }
+}
+
+/// Exit with `code` once the typewriter has caught up, the pager, if any, has been closed
+/// and the audit log has been written
+pub fn exit(code: i32) -> ! {
+    audit::flush();
+    finish_typewriter();
+    finish_pager();
+    process::exit(code);
+}
+
+/// Write to stdout or the pager, exiting quietly once the reader has gone away.
+///
+/// Exiting also drops any translation still in flight, so a closed pipe as in
+/// `tzh t --stream file | head` stops further requests instead of panicking.
+pub fn write_stdout(text: &str) {
+    if let Some(file) = OUTPUT_FILE.lock().unwrap().as_mut() {
+        if let Err(e) = file.write_all(text.as_bytes()) {
+            eprintln!("Failed to write output: {}", e);
+            exit(1);
+        }
+        return;
+    }
+    if let Some(typewriter) = TYPEWRITER.lock().unwrap().as_ref() {
+        // The thread only stops once the sender is gone
+        let _ = typewriter.sender.send(Typed::Stdout(text.to_string()));
+        return;
+    }
+
+    let result = match PAGER.lock().unwrap().as_mut() {
+        Some(pager) => pager
+            .stdin
+            .write_all(text.as_bytes())
+            .and_then(|_| pager.stdin.flush()),
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout
+                .write_all(text.as_bytes())
+                .and_then(|_| stdout.flush())
+        }
+    };
+
+    if let Err(e) = result {
+        if e.kind() == ErrorKind::BrokenPipe {
+            exit(BROKEN_PIPE_STATUS);
+        }
+        eprintln!("Failed to write output: {}", e);
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::RecordingSleeper;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Collects stdout and stderr in one buffer, to check their order
+    #[derive(Clone, Default)]
+    struct Terminal(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Terminal {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn typed(queue: Vec<Typed>, delay: Duration) -> (String, Vec<Duration>) {
+        let (sender, receiver) = tokio_mpsc::unbounded_channel();
+        for typed in queue {
+            sender.send(typed).unwrap();
+        }
+        drop(sender);
+
+        let terminal = Terminal::default();
+        let sleeper = RecordingSleeper::default();
+        type_out(
+            receiver,
+            &mut terminal.clone(),
+            &mut terminal.clone(),
+            &sleeper,
+            delay,
+        )
+        .await
+        .unwrap();
+        let output = String::from_utf8(terminal.0.take()).unwrap();
+        (output, sleeper.slept())
+    }
+
+    #[tokio::test]
+    async fn waits_after_every_character() {
+        let delay = Duration::from_millis(30);
+        let (output, slept) = typed(vec![Typed::Stdout("你好\n".to_string())], delay).await;
+        assert_eq!(output, "你好\n");
+        assert_eq!(slept, [delay; 3]);
+    }
+
+    #[tokio::test]
+    async fn color_codes_are_written_without_delay() {
+        let delay = Duration::from_millis(30);
+        let (output, slept) =
+            typed(vec![Typed::Stdout("\x1b[31mab\x1b[0m".to_string())], delay).await;
+        assert_eq!(output, "\x1b[31mab\x1b[0m");
+        assert_eq!(slept, [delay; 2]);
+    }
+
+    #[tokio::test]
+    async fn stderr_stays_in_order_with_typed_output() {
+        let queue = vec![
+            Typed::Stdout("one\n".to_string()),
+            Typed::Stderr("Line 2 failed\n".to_string()),
+            Typed::Stdout("three\n".to_string()),
+        ];
+        let (output, slept) = typed(queue, Duration::from_millis(5)).await;
+        assert_eq!(output, "one\nLine 2 failed\nthree\n");
+        // Only stdout is typed out
+        assert_eq!(slept.len(), 10);
+    }
+}
@@ -0,0 +1,142 @@
+use regex::Regex;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Added to the system prompt when the text contains protected placeholders
+pub const PROMPT_RULE: &str = "\n- Keep placeholders like ⟦1⟧ exactly as written; you may move them where the grammar needs them";
+
+/// Format placeholders: Mustache/Handlebars, .NET/ICU braces, Python and printf styles
+fn pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(concat!(
+            r"\{\{\{[^{}]*\}\}\}|\{\{[^{}]*\}\}",
+            r"|\{[A-Za-z0-9_.]*(?:[,:][^{}]*)?\}",
+            r"|%\([A-Za-z0-9_]+\)[-+0#]*\d*(?:\.\d+)?[diouxXeEfFgGcrs]",
+            r"|%(?:\d+\$)?[-+0#']*(?:\d+|\*)?(?:\.(?:\d+|\*))?(?:hh|h|ll|l|L|z|j|t|q)?[diouxXeEfFgGaAcspn@%]",
+        ))
+        .expect("Invalid placeholder pattern")
+    })
+}
+
+/// Text with its placeholders swapped for numbered sentinels such as `⟦1⟧`
+#[derive(Debug)]
+pub struct Protected {
+    text: String,
+    placeholders: Vec<String>,
+}
+
+/// A translation dropped or duplicated one of the protected placeholders
+#[derive(Debug)]
+pub struct PlaceholderLost {
+    placeholder: String,
+}
+
+impl fmt::Display for PlaceholderLost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Translation did not keep the placeholder {} exactly once",
+            self.placeholder
+        )
+    }
+}
+
+impl std::error::Error for PlaceholderLost {}
+
+impl Protected {
+    /// Replace every placeholder in `text`, or None if there are none
+    pub fn new(text: &str) -> Option<Self> {
+        let mut placeholders = Vec::new();
+        let protected = pattern().replace_all(text, |captures: &regex::Captures| {
+            placeholders.push(captures[0].to_string());
+            sentinel(placeholders.len())
+        });
+
+        if placeholders.is_empty() {
+            return None;
+        }
+        Some(Self {
+            text: protected.into_owned(),
+            placeholders,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Put the original placeholders back, failing if any sentinel is missing or repeated
+    pub fn restore(&self, translation: &str) -> Result<String, PlaceholderLost> {
+        let mut restored = translation.to_string();
+        for (index, placeholder) in self.placeholders.iter().enumerate() {
+            let sentinel = sentinel(index + 1);
+            if restored.matches(&sentinel).count() != 1 {
+                return Err(PlaceholderLost {
+                    placeholder: placeholder.clone(),
+                });
+            }
+            restored = restored.replacen(&sentinel, placeholder, 1);
+        }
+        Ok(restored)
+    }
+}
+
+/// Whether text comes from `Protected::text`
+pub fn has_sentinels(text: &str) -> bool {
+    text.contains('⟦')
+}
+
+fn sentinel(number: usize) -> String {
+    format!("⟦{}⟧", number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protected(text: &str) -> String {
+        Protected::new(text).unwrap().text().to_string()
+    }
+
+    #[test]
+    fn protects_every_placeholder_style() {
+        assert_eq!(
+            protected("Hello {0}, you have {count} items"),
+            "Hello ⟦1⟧, you have ⟦2⟧ items"
+        );
+        assert_eq!(
+            protected("Hi {{user.name}} and {{{raw}}}"),
+            "Hi ⟦1⟧ and ⟦2⟧"
+        );
+        assert_eq!(protected("Total: {0:N2} ({1,10})"), "Total: ⟦1⟧ (⟦2⟧)");
+        assert_eq!(protected("%s has %1$d of %.2f%%"), "⟦1⟧ has ⟦2⟧ of ⟦3⟧⟦4⟧");
+        assert_eq!(protected("%(user)s left"), "⟦1⟧ left");
+    }
+
+    #[test]
+    fn text_without_placeholders_is_not_protected() {
+        assert!(Protected::new("Plain text, 100% done").is_none());
+        assert!(Protected::new("A {sentence with spaces}").is_none());
+    }
+
+    #[test]
+    fn restores_placeholders_moved_by_the_translation() {
+        let text = Protected::new("{name} has {count} messages").unwrap();
+        assert_eq!(
+            text.restore("⟦2⟧ Nachrichten für ⟦1⟧").unwrap(),
+            "{count} Nachrichten für {name}"
+        );
+    }
+
+    #[test]
+    fn dropped_or_repeated_placeholder_fails() {
+        let text = Protected::new("{name} has {count} messages").unwrap();
+        let error = text.restore("⟦1⟧ hat Nachrichten").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Translation did not keep the placeholder {count} exactly once"
+        );
+        assert!(text.restore("⟦1⟧ ⟦1⟧ ⟦2⟧").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+/// Named prompt style, loaded from `presets/<name>.toml` in the config directory.
+///
+/// Prompt templates may use `{text}`, `{target}` and `{source}`, where `{source}` is the
+/// source language name or "the source language" when it is auto-detected.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    /// File name without the extension
+    #[serde(skip)]
+    pub name: String,
+    pub description: Option<String>,
+    /// Replaces the system prompt for text translation
+    pub system_prompt: Option<String>,
+    /// Replaces the request for text translation
+    pub prompt: Option<String>,
+    /// Replaces the system prompt for word lookups
+    pub word_system_prompt: Option<String>,
+    /// Replaces the request for word lookups
+    pub word_prompt: Option<String>,
+    /// Extra rules added to either system prompt, such as a register or terminology
+    pub rules: Vec<String>,
+    pub temperature: Option<f32>,
+}
+
+impl Preset {
+    pub fn dir() -> Result<PathBuf> {
+        Ok(Config::dir()?.join("presets"))
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Self::dir()?.join(format!("{}.toml", name));
+        if !path.exists() {
+            bail!(
+                "No preset named {:?} (expected {}, see `tzh presets list`)",
+                name,
+                path.display()
+            );
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut preset: Preset = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse preset {}", path.display()))?;
+        preset.name = name.to_string();
+
+        for template in [&preset.prompt, &preset.word_prompt].into_iter().flatten() {
+            if !template.contains("{text}") {
+                bail!("Preset {:?}: prompts must contain {{text}}", name);
+            }
+        }
+        Ok(preset)
+    }
+
+    /// All presets in the presets directory, sorted by name
+    pub fn list() -> Result<Vec<Self>> {
+        let dir = Self::dir()?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+        for entry in
+            fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "toml")
+                && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+            {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+
+        names.iter().map(|name| Self::load(name)).collect()
+    }
+
+    /// The system prompt replacing the built-in one for words or text, if any
+    pub fn system_prompt(&self, word: bool) -> Option<&str> {
+        if word {
+            self.word_system_prompt.as_deref()
+        } else {
+            self.system_prompt.as_deref()
+        }
+    }
+
+    /// The request for words or text filled in, if the preset replaces it
+    pub fn user_prompt(
+        &self,
+        word: bool,
+        text: &str,
+        target: &str,
+        source: Option<&str>,
+    ) -> Option<String> {
+        let template = if word {
+            self.word_prompt.as_deref()
+        } else {
+            self.prompt.as_deref()
+        }?;
+
+        // The text goes last so braces inside it are never taken for placeholders
+        Some(
+            template
+                .replace("{target}", target)
+                .replace("{source}", source.unwrap_or("the source language"))
+                .replace("{text}", text),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_the_prompt_template() {
+        let preset = Preset {
+            prompt: Some("From {source} to {target}: {text}".to_string()),
+            ..Preset::default()
+        };
+        assert_eq!(
+            preset.user_prompt(false, "Hi", "German", Some("English")),
+            Some("From English to German: Hi".to_string())
+        );
+        assert_eq!(
+            preset.user_prompt(false, "Hi", "German", None),
+            Some("From the source language to German: Hi".to_string())
+        );
+        assert_eq!(preset.user_prompt(true, "Hi", "German", None), None);
+    }
+
+    #[test]
+    fn braces_in_the_text_are_not_placeholders() {
+        let preset = Preset {
+            prompt: Some("{target}: {text}".to_string()),
+            ..Preset::default()
+        };
+        assert_eq!(
+            preset.user_prompt(false, "Use {target} here", "German", None),
+            Some("German: Use {target} here".to_string())
+        );
+    }
+
+    #[test]
+    fn word_prompts_are_separate() {
+        let preset = Preset {
+            system_prompt: Some("text".to_string()),
+            word_system_prompt: Some("word".to_string()),
+            ..Preset::default()
+        };
+        assert_eq!(preset.system_prompt(false), Some("text"));
+        assert_eq!(preset.system_prompt(true), Some("word"));
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(toml::from_str::<Preset>("temprature = 0.5").is_err());
+        let preset: Preset = toml::from_str("temperature = 0.5\nrules = [\"Formal\"]").unwrap();
+        assert_eq!(preset.temperature, Some(0.5));
+        assert_eq!(preset.rules, ["Formal"]);
+    }
+}
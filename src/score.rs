@@ -0,0 +1,290 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::tokens::is_cjk;
+
+/// Highest word n-gram order used by BLEU
+const BLEU_ORDER: usize = 4;
+/// Highest character n-gram order used by chrF
+const CHRF_ORDER: usize = 6;
+/// Weight of recall relative to precision in chrF
+const CHRF_BETA: f64 = 2.0;
+
+/// Scores of one candidate line against its reference, from 0 to 100
+#[derive(Debug, Serialize)]
+pub struct LineScore {
+    pub line: usize,
+    pub chrf: f64,
+    pub bleu: f64,
+}
+
+/// Per-line and corpus-level scores of a candidate file
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub lines: Vec<LineScore>,
+    pub chrf: f64,
+    pub bleu: f64,
+}
+
+/// Score candidate lines against reference lines of the same index.
+///
+/// Corpus scores are computed from statistics summed over all lines rather than
+/// by averaging the sentence scores.
+pub fn score(candidates: &[&str], references: &[&str]) -> Report {
+    let mut bleu_total = BleuStats::default();
+    let mut chrf_total = ChrfStats::default();
+    let mut lines = Vec::new();
+
+    for (index, (candidate, reference)) in candidates.iter().zip(references).enumerate() {
+        let bleu = BleuStats::new(candidate, reference);
+        let chrf = ChrfStats::new(candidate, reference);
+
+        lines.push(LineScore {
+            line: index + 1,
+            chrf: chrf.score(),
+            bleu: bleu.score(true),
+        });
+        bleu_total.add(&bleu);
+        chrf_total.add(&chrf);
+    }
+
+    Report {
+        lines,
+        chrf: chrf_total.score(),
+        bleu: bleu_total.score(false),
+    }
+}
+
+/// Split text into words and punctuation, with every CJK character a word of its own
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+
+    for c in text.chars() {
+        if c.is_alphanumeric() && !is_cjk(c) {
+            word.push(c);
+            continue;
+        }
+
+        if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+        if !c.is_whitespace() {
+            tokens.push(c.to_string());
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+
+    tokens
+}
+
+#[derive(Debug, Default)]
+struct BleuStats {
+    /// Matching and total candidate n-grams for each order
+    matches: [usize; BLEU_ORDER],
+    totals: [usize; BLEU_ORDER],
+    candidate_len: usize,
+    reference_len: usize,
+}
+
+impl BleuStats {
+    fn new(candidate: &str, reference: &str) -> Self {
+        let candidate = tokenize(candidate);
+        let reference = tokenize(reference);
+        let mut stats = Self {
+            candidate_len: candidate.len(),
+            reference_len: reference.len(),
+            ..Self::default()
+        };
+
+        for order in 1..=BLEU_ORDER {
+            let (matches, total) = overlap(&ngrams(&candidate, order), &ngrams(&reference, order));
+            stats.matches[order - 1] = matches;
+            stats.totals[order - 1] = total;
+        }
+        stats
+    }
+
+    fn add(&mut self, other: &Self) {
+        for order in 0..BLEU_ORDER {
+            self.matches[order] += other.matches[order];
+            self.totals[order] += other.totals[order];
+        }
+        self.candidate_len += other.candidate_len;
+        self.reference_len += other.reference_len;
+    }
+
+    /// BLEU with a brevity penalty.
+    ///
+    /// Sentence scores use add-one smoothing for orders above unigrams, since a
+    /// single missing 4-gram would otherwise zero out a short line.
+    fn score(&self, smooth: bool) -> f64 {
+        if self.candidate_len == 0 {
+            return 0.0;
+        }
+
+        let mut log_precision = 0.0;
+        for order in 0..BLEU_ORDER {
+            let (matches, total) = if smooth && order > 0 {
+                (self.matches[order] + 1, self.totals[order] + 1)
+            } else {
+                (self.matches[order], self.totals[order])
+            };
+            if matches == 0 {
+                return 0.0;
+            }
+            log_precision += (matches as f64 / total as f64).ln() / BLEU_ORDER as f64;
+        }
+
+        let brevity = if self.candidate_len < self.reference_len {
+            1.0 - self.reference_len as f64 / self.candidate_len as f64
+        } else {
+            0.0
+        };
+
+        100.0 * (brevity + log_precision).exp()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChrfStats {
+    /// Matching character n-grams and n-gram totals of the candidate and reference
+    matches: [usize; CHRF_ORDER],
+    candidate: [usize; CHRF_ORDER],
+    reference: [usize; CHRF_ORDER],
+}
+
+impl ChrfStats {
+    fn new(candidate: &str, reference: &str) -> Self {
+        // chrF ignores whitespace
+        let candidate: Vec<char> = candidate.chars().filter(|c| !c.is_whitespace()).collect();
+        let reference: Vec<char> = reference.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut stats = Self::default();
+
+        for order in 1..=CHRF_ORDER {
+            let reference_ngrams = ngrams(&reference, order);
+            let (matches, total) = overlap(&ngrams(&candidate, order), &reference_ngrams);
+            stats.matches[order - 1] = matches;
+            stats.candidate[order - 1] = total;
+            stats.reference[order - 1] = reference_ngrams.values().sum();
+        }
+        stats
+    }
+
+    fn add(&mut self, other: &Self) {
+        for order in 0..CHRF_ORDER {
+            self.matches[order] += other.matches[order];
+            self.candidate[order] += other.candidate[order];
+            self.reference[order] += other.reference[order];
+        }
+    }
+
+    /// F-score of character n-gram precision and recall, averaged over orders
+    fn score(&self) -> f64 {
+        let mut precision = 0.0;
+        let mut recall = 0.0;
+        let mut orders = 0;
+
+        for order in 0..CHRF_ORDER {
+            // Orders longer than both texts carry no information
+            if self.candidate[order] == 0 && self.reference[order] == 0 {
+                continue;
+            }
+            orders += 1;
+            if self.candidate[order] > 0 {
+                precision += self.matches[order] as f64 / self.candidate[order] as f64;
+            }
+            if self.reference[order] > 0 {
+                recall += self.matches[order] as f64 / self.reference[order] as f64;
+            }
+        }
+
+        if orders == 0 {
+            return 0.0;
+        }
+        precision /= orders as f64;
+        recall /= orders as f64;
+        if precision + recall == 0.0 {
+            return 0.0;
+        }
+
+        let beta2 = CHRF_BETA * CHRF_BETA;
+        100.0 * (1.0 + beta2) * precision * recall / (beta2 * precision + recall)
+    }
+}
+
+/// Count every n-gram of the given order
+fn ngrams<T: Eq + Hash + Clone>(items: &[T], order: usize) -> HashMap<Vec<T>, usize> {
+    let mut counts = HashMap::new();
+    for window in items.windows(order) {
+        *counts.entry(window.to_vec()).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Clipped matches of candidate n-grams in the reference, and the candidate n-gram total
+fn overlap<T: Eq + Hash>(
+    candidate: &HashMap<Vec<T>, usize>,
+    reference: &HashMap<Vec<T>, usize>,
+) -> (usize, usize) {
+    candidate
+        .iter()
+        .fold((0, 0), |(matches, total), (ngram, count)| {
+            let clipped = (*count).min(reference.get(ngram).copied().unwrap_or(0));
+            (matches + clipped, total + count)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() < 0.01
+    }
+
+    #[test]
+    fn tokenizes_words_punctuation_and_cjk_characters() {
+        assert_eq!(tokenize("Hello, world!"), ["Hello", ",", "world", "!"]);
+        assert_eq!(tokenize("我爱 Rust"), ["我", "爱", "Rust"]);
+    }
+
+    #[test]
+    fn identical_lines_score_100() {
+        let report = score(&["The cat sat on the mat."], &["The cat sat on the mat."]);
+        assert!(close(report.bleu, 100.0), "{}", report.bleu);
+        assert!(close(report.chrf, 100.0), "{}", report.chrf);
+        assert!(close(report.lines[0].bleu, 100.0));
+    }
+
+    #[test]
+    fn unrelated_lines_score_0() {
+        let report = score(&["abc"], &["xyz"]);
+        assert_eq!(report.bleu, 0.0);
+        assert_eq!(report.chrf, 0.0);
+    }
+
+    #[test]
+    fn short_candidate_gets_a_brevity_penalty() {
+        let report = score(&["the cat"], &["the cat sat"]);
+        // Every smoothed precision is 1, leaving exp(1 - 3/2)
+        assert!(close(report.lines[0].bleu, 100.0 * (-0.5f64).exp()));
+        assert!(report.lines[0].chrf < 100.0 && report.lines[0].chrf > 0.0);
+    }
+
+    #[test]
+    fn corpus_score_sums_statistics_over_lines() {
+        let report = score(
+            &["the cat sat on the mat", "xyz"],
+            &["the cat sat on the mat", "abc"],
+        );
+        assert_eq!(report.lines.len(), 2);
+        assert!(close(report.lines[0].chrf, 100.0));
+        assert_eq!(report.lines[1].chrf, 0.0);
+        // The long matching line weighs more than the short miss
+        assert!(report.chrf > 50.0, "{}", report.chrf);
+    }
+}
@@ -0,0 +1,202 @@
+use clap::ValueEnum;
+
+/// How sentence boundaries are found
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Segmenter {
+    /// Every sentence-ending punctuation mark ends a sentence
+    #[default]
+    Simple,
+    /// Also recognizes abbreviations, initials and ellipses within a sentence
+    Smart,
+}
+
+/// Punctuation that ends a sentence when followed by whitespace
+const TERMINATORS: &[char] = &['.', '!', '?', '…'];
+
+/// Full-width punctuation that ends a sentence even without following whitespace
+const CJK_TERMINATORS: &[char] = &['。', '！', '？'];
+
+/// Closing quotes and brackets that belong to the sentence they follow
+const CLOSERS: &[char] = &['"', '\'', '”', '’', ')', '）', '」', '』', ']'];
+
+/// Abbreviations, without their final period, after which a sentence goes on
+fn abbreviations(lang: Option<&str>) -> &'static [&'static str] {
+    match lang.map(|lang| lang.split('-').next().unwrap_or(lang)) {
+        Some("de") => &[
+            "z.b", "bzw", "usw", "ca", "nr", "dr", "prof", "vgl", "evtl", "d.h",
+        ],
+        Some("fr") => &["m", "mme", "mlle", "dr", "etc", "cf", "p.ex", "env", "av"],
+        Some("es") => &["sr", "sra", "srta", "dr", "dra", "etc", "p.ej", "ud", "uds"],
+        _ => &[
+            "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "etc", "e.g", "i.e", "fig",
+            "no", "approx", "inc", "ltd", "co", "dept", "est", "jan", "feb", "mar", "apr", "jun",
+            "jul", "aug", "sep", "sept", "oct", "nov", "dec",
+        ],
+    }
+}
+
+/// Split text into sentences, keeping their punctuation and closing quotes
+pub fn segment_sentences(text: &str, lang: Option<&str>, segmenter: Segmenter) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for end in boundaries(text, lang, segmenter) {
+        push_sentence(&mut sentences, &text[start..end]);
+        start = end;
+    }
+    push_sentence(&mut sentences, &text[start..]);
+
+    sentences
+}
+
+/// Whether a sentence ends between two pieces of text that are joined with a space
+pub fn breaks_between(before: &str, after: &str, lang: Option<&str>, segmenter: Segmenter) -> bool {
+    let joined = format!("{} {}", before, after);
+    boundaries(&joined, lang, segmenter).contains(&before.len())
+}
+
+/// Byte offsets right after each sentence-ending mark and its closing quotes
+fn boundaries(text: &str, lang: Option<&str>, segmenter: Segmenter) -> Vec<usize> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut boundaries = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (_, c) = chars[i];
+        let cjk = CJK_TERMINATORS.contains(&c);
+        if !cjk && !TERMINATORS.contains(&c) {
+            i += 1;
+            continue;
+        }
+
+        // Take repeated marks such as `?!` or `...` and any closing quotes along
+        let mut end = i + 1;
+        while end < chars.len() && TERMINATORS.contains(&chars[end].1) {
+            end += 1;
+        }
+        while end < chars.len() && CLOSERS.contains(&chars[end].1) {
+            end += 1;
+        }
+
+        let at_end = end == chars.len();
+        let before_space = !at_end && chars[end].1.is_whitespace();
+        if (cjk || at_end || before_space)
+            && (segmenter == Segmenter::Simple || is_boundary(text, &chars, i, end, lang))
+        {
+            boundaries.push(chars.get(end).map_or(text.len(), |(index, _)| *index));
+        }
+        i = end;
+    }
+
+    boundaries
+}
+
+fn push_sentence(sentences: &mut Vec<String>, sentence: &str) {
+    let sentence = sentence.trim();
+    if !sentence.is_empty() {
+        sentences.push(sentence.to_string());
+    }
+}
+
+/// Smart check of a candidate boundary at the punctuation `chars[mark..end]`
+fn is_boundary(
+    text: &str,
+    chars: &[(usize, char)],
+    mark: usize,
+    end: usize,
+    lang: Option<&str>,
+) -> bool {
+    let next = chars[end..]
+        .iter()
+        .map(|(_, c)| *c)
+        .find(|c| !c.is_whitespace());
+    let Some(next) = next else {
+        return true;
+    };
+
+    let punctuation: String = chars[mark..end].iter().map(|(_, c)| *c).collect();
+    let ellipsis = punctuation.starts_with("..") || punctuation.starts_with('…');
+    let lowercase_next = next.is_lowercase();
+
+    if ellipsis || punctuation == "." {
+        // An ellipsis or abbreviation followed by a lowercase word continues the sentence
+        if lowercase_next {
+            return false;
+        }
+        if punctuation == "." {
+            return !ends_with_abbreviation(&text[..chars[end - 1].0 + 1], lang);
+        }
+    }
+    true
+}
+
+/// Whether text ending in a period ends with an abbreviation or an initial like `J.`
+fn ends_with_abbreviation(text: &str, lang: Option<&str>) -> bool {
+    let word = text
+        .trim_end_matches(CLOSERS)
+        .trim_end_matches('.')
+        .rsplit(|c: char| c.is_whitespace() || c == '(' || c == '"')
+        .next()
+        .unwrap_or("");
+
+    let mut letters = word.chars();
+    let initial = matches!((letters.next(), letters.next()), (Some(c), None) if c.is_uppercase());
+    initial || abbreviations(lang).contains(&word.to_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn smart(text: &str) -> Vec<String> {
+        segment_sentences(text, None, Segmenter::Smart)
+    }
+
+    #[test]
+    fn splits_at_terminators_and_keeps_closing_quotes() {
+        let sentences =
+            segment_sentences("It works. Does it?! \"Yes.\" Fine", None, Segmenter::Simple);
+        assert_eq!(sentences, ["It works.", "Does it?!", "\"Yes.\"", "Fine"]);
+    }
+
+    #[test]
+    fn decimals_and_versions_do_not_end_sentences() {
+        assert_eq!(smart("Pi is 3.14 or so. Use v1.2.3 now.").len(), 2);
+    }
+
+    #[test]
+    fn cjk_marks_end_sentences_without_spaces() {
+        assert_eq!(
+            segment_sentences("你好。今天好吗？很好！", Some("zh"), Segmenter::Simple),
+            ["你好。", "今天好吗？", "很好！"]
+        );
+    }
+
+    #[test]
+    fn smart_skips_abbreviations_initials_and_ellipses() {
+        assert_eq!(smart("Mr. Smith met Dr. Jones. They talked.").len(), 2);
+        assert_eq!(smart("Use e.g. a list. Or not.").len(), 2);
+        assert_eq!(smart("J. R. R. Tolkien wrote it.").len(), 1);
+        assert_eq!(smart("Well... maybe. Or... No.").len(), 3);
+        assert_eq!(
+            segment_sentences("Mr. Smith left.", None, Segmenter::Simple).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn abbreviations_depend_on_the_language() {
+        let text = "Das ist z.B. Käse. Ja.";
+        assert_eq!(
+            segment_sentences(text, Some("de-DE"), Segmenter::Smart).len(),
+            2
+        );
+        assert_eq!(segment_sentences(text, None, Segmenter::Smart).len(), 3);
+    }
+
+    #[test]
+    fn break_between_joined_lines() {
+        assert!(breaks_between("It ended.", "Then", None, Segmenter::Smart));
+        assert!(!breaks_between("Ask Mr.", "Smith", None, Segmenter::Smart));
+        assert!(!breaks_between("It goes", "on", None, Segmenter::Simple));
+    }
+}
@@ -0,0 +1,58 @@
+/// Collects the `data:` payloads of a server-sent event stream from chunks split anywhere,
+/// even inside a line or a multi-byte character
+#[derive(Debug, Default)]
+pub struct EventBuffer {
+    pending: Vec<u8>,
+}
+
+impl EventBuffer {
+    /// Add the next chunk of the stream, returning the payloads of the lines it completes
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+
+        let mut payloads = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            // Event names, ids and `:` comments carry nothing a translation needs
+            if let Some(payload) = line.trim_end().strip_prefix("data:") {
+                payloads.push(payload.trim_start().to_string());
+            }
+        }
+        payloads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yields_data_lines() {
+        let mut events = EventBuffer::default();
+        let payloads = events.push(b"data: {\"a\":1}\n\ndata: [DONE]\n\n");
+        assert_eq!(payloads, ["{\"a\":1}", "[DONE]"]);
+    }
+
+    #[test]
+    fn waits_for_the_end_of_a_line() {
+        let mut events = EventBuffer::default();
+        assert!(events.push(b"data: hel").is_empty());
+        assert_eq!(events.push(b"lo\r\n"), ["hello"]);
+    }
+
+    #[test]
+    fn keeps_characters_split_across_chunks() {
+        let bytes = "data: 你好\n".as_bytes();
+        let mut events = EventBuffer::default();
+        assert!(events.push(&bytes[..8]).is_empty());
+        assert_eq!(events.push(&bytes[8..]), ["你好"]);
+    }
+
+    #[test]
+    fn skips_comments_and_other_fields() {
+        let mut events = EventBuffer::default();
+        let payloads = events.push(b": keep-alive\nevent: message\nid: 1\ndata:x\n");
+        assert_eq!(payloads, ["x"]);
+    }
+}
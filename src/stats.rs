@@ -0,0 +1,176 @@
+use colored::Colorize;
+use std::time::Duration;
+
+use crate::config::Pricing;
+use crate::theme::Themed;
+use crate::tokens;
+use crate::translator::Translation;
+
+/// Totals of an interactive session, summarized when it ends
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    translated: usize,
+    failed: usize,
+    characters: usize,
+    input_tokens: usize,
+    output_tokens: usize,
+    /// Translations whose token usage the endpoint did not report, so it was estimated
+    estimated: usize,
+    latency: Duration,
+}
+
+impl SessionStats {
+    pub fn record(&mut self, text: &str, translation: &Translation, latency: Duration) {
+        self.translated += 1;
+        self.characters += text.chars().count();
+        self.latency += latency;
+
+        match (translation.input_tokens, translation.output_tokens) {
+            (Some(input), Some(output)) => {
+                self.input_tokens += input;
+                self.output_tokens += output;
+            }
+            // Only the text itself can be estimated, not the prompt around it
+            _ => {
+                self.input_tokens += tokens::estimate_tokens(text);
+                self.output_tokens += tokens::estimate_tokens(&translation.text);
+                self.estimated += 1;
+            }
+        }
+    }
+
+    pub fn record_failure(&mut self) {
+        self.failed += 1;
+    }
+
+    fn tokens_line(&self) -> String {
+        let estimated = if self.estimated == self.translated {
+            " (estimated)"
+        } else if self.estimated > 0 {
+            " (partly estimated)"
+        } else {
+            ""
+        };
+        format!(
+            "Tokens: {} input, {} output{}",
+            self.input_tokens, self.output_tokens, estimated
+        )
+    }
+
+    /// Print the recap, with the cost if pricing is configured
+    pub fn print_summary(&self, pricing: Option<&Pricing>) {
+        println!("{}", "Session summary:".header().bold());
+
+        let failed = if self.failed > 0 {
+            format!(" ({} failed)", self.failed)
+        } else {
+            String::new()
+        };
+        println!("  Translations: {}{}", self.translated, failed);
+        println!("  Characters: {}", self.characters);
+        if self.translated == 0 {
+            return;
+        }
+
+        println!("  {}", self.tokens_line());
+        if let Some(pricing) = pricing {
+            println!(
+                "  Estimated cost: {:.4}",
+                pricing.cost(self.input_tokens, self.output_tokens)
+            );
+        }
+        println!(
+            "  Average latency: {:.2}s",
+            self.latency.as_secs_f64() / self.translated as f64
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::TextKind;
+    use crate::config::Config;
+    use crate::translator::Translator;
+    use serde_json::json;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn reported(text: &str, input: usize, output: usize) -> Translation {
+        Translation {
+            input_tokens: Some(input),
+            output_tokens: Some(output),
+            ..Translation::new(text)
+        }
+    }
+
+    #[test]
+    fn adds_up_reported_usage() {
+        let mut stats = SessionStats::default();
+        stats.record(
+            "Hallo",
+            &reported("Hello", 30, 4),
+            Duration::from_millis(200),
+        );
+        stats.record(
+            "Tschüss",
+            &reported("Bye", 32, 3),
+            Duration::from_millis(400),
+        );
+        stats.record_failure();
+
+        assert_eq!((stats.translated, stats.failed), (2, 1));
+        assert_eq!(stats.characters, 12);
+        assert_eq!((stats.input_tokens, stats.output_tokens), (62, 7));
+        assert_eq!(stats.estimated, 0);
+        assert_eq!(stats.latency, Duration::from_millis(600));
+    }
+
+    #[test]
+    fn estimates_missing_usage_from_the_text() {
+        let mut stats = SessionStats::default();
+        let text = "Ein ganzer Satz zum Übersetzen";
+        let translation = Translation::new("A whole sentence to translate");
+        stats.record(text, &translation, Duration::ZERO);
+
+        assert_eq!(stats.input_tokens, tokens::estimate_tokens(text));
+        assert_eq!(
+            stats.output_tokens,
+            tokens::estimate_tokens(&translation.text)
+        );
+        assert_eq!(stats.estimated, 1);
+    }
+
+    #[test]
+    fn marks_estimated_tokens() {
+        let mut stats = SessionStats::default();
+        stats.record("Hallo", &reported("Hello", 30, 4), Duration::ZERO);
+        assert_eq!(stats.tokens_line(), "Tokens: 30 input, 4 output");
+        stats.record("Welt", &Translation::new("World"), Duration::ZERO);
+        assert!(stats.tokens_line().ends_with(" (partly estimated)"));
+    }
+
+    #[tokio::test]
+    async fn streamed_usage_is_not_estimated() {
+        let server = MockServer::start().await;
+        let body = format!(
+            "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+            json!({ "choices": [{ "delta": { "content": "Hallo" } }] }),
+            json!({ "choices": [], "usage": { "prompt_tokens": 42, "completion_tokens": 3 } }),
+        );
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+        let mut config = Config::default();
+        config.set_endpoint(&server.uri());
+
+        let translation = Translator::new(&config)
+            .translate_live("Hello", TextKind::Line, "de", None, |_| {})
+            .await
+            .unwrap();
+        let mut stats = SessionStats::default();
+        stats.record("Hello", &translation, Duration::from_millis(300));
+        assert_eq!(stats.tokens_line(), "Tokens: 42 input, 3 output");
+    }
+}
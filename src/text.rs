@@ -0,0 +1,296 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::segmentation::{self, Segmenter};
+
+/// Convert CRLF and lone CR line endings to LF
+pub fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Line ending used by the given text, preferring CRLF if any line uses it
+pub fn detect_line_ending(text: &str) -> &'static str {
+    if text.contains("\r\n") { "\r\n" } else { "\n" }
+}
+
+/// Characters that end a sentence, after which a wrapped line is not joined
+const SENTENCE_END: &[char] = &[
+    '.', '!', '?', ':', ';', '。', '！', '？', '：', '；', '…', '"', '”', '’', ')', '）',
+];
+
+/// Join hard-wrapped lines into logical lines.
+///
+/// Consecutive lines are joined when the previous one does not end a sentence.
+/// Blank lines, list items and indented code always keep their own line break.
+pub fn unwrap_lines(text: &str, lang: Option<&str>, segmenter: Segmenter) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    // Whether the last logical line is prose that may have the next line appended
+    let mut prose = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim_end();
+
+        if trimmed.is_empty() || is_code_line(line) {
+            lines.push(trimmed.to_string());
+            prose = false;
+            continue;
+        }
+
+        let next = trimmed.trim_start();
+        match lines.last_mut() {
+            Some(last)
+                if prose && !is_list_item(line) && !ends_sentence(last, next, lang, segmenter) =>
+            {
+                if needs_space(last, next) {
+                    last.push(' ');
+                }
+                last.push_str(next);
+            }
+            _ => lines.push(trimmed.to_string()),
+        }
+        prose = true;
+    }
+
+    lines
+}
+
+/// Whether a sentence ends between a line and the line wrapped after it.
+/// The smart segmenter does not break after abbreviations such as `e.g.`.
+fn ends_sentence(line: &str, next: &str, lang: Option<&str>, segmenter: Segmenter) -> bool {
+    if !line.ends_with(SENTENCE_END) {
+        return false;
+    }
+    if segmenter == Segmenter::Smart && line.ends_with(['.', '…']) {
+        return segmentation::breaks_between(line, next, lang, segmenter);
+    }
+    true
+}
+
+/// Width in terminal columns of the widest line, used to re-wrap output
+pub fn max_line_width(text: &str) -> usize {
+    text.lines()
+        .map(|line| UnicodeWidthStr::width(line.trim_end()))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Right-to-left embedding and pop directional formatting controls
+const RLE: char = '\u{202B}';
+const PDF: char = '\u{202C}';
+
+/// Whether a language is written right to left
+pub fn is_rtl_language(code: &str) -> bool {
+    let base = code.split('-').next().unwrap_or(code);
+    matches!(base, "ar" | "he" | "iw" | "fa" | "ur" | "yi" | "ps")
+}
+
+/// Wrap every line in a right-to-left embedding so terminals lay out mixed
+/// RTL and LTR text correctly. The logical text itself is unchanged.
+pub fn embed_rtl(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}{}", RLE, line, PDF)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Greedily wrap text to `width` columns, breaking at spaces or between CJK characters
+pub fn wrap_to_width(text: &str, width: usize) -> String {
+    let mut wrapped = Vec::new();
+
+    for source_line in text.lines() {
+        let mut line = String::new();
+        let mut line_width = 0;
+
+        for token in tokens(source_line) {
+            let token_width = UnicodeWidthStr::width(token);
+            if token == " " && line.is_empty() {
+                continue;
+            }
+
+            if line_width + token_width > width && !line.trim().is_empty() {
+                wrapped.push(line.trim_end().to_string());
+                line.clear();
+                line_width = 0;
+                if token == " " {
+                    continue;
+                }
+            }
+
+            line.push_str(token);
+            line_width += token_width;
+        }
+        wrapped.push(line.trim_end().to_string());
+    }
+
+    wrapped.join("\n")
+}
+
+/// Split a line into words, single spaces and individual wide characters
+fn tokens(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (index, c) in line.char_indices() {
+        let wide = c.width().unwrap_or(0) > 1;
+        if c == ' ' || wide {
+            if let Some(word_start) = start.take() {
+                tokens.push(&line[word_start..index]);
+            }
+            tokens.push(&line[index..index + c.len_utf8()]);
+        } else if start.is_none() {
+            start = Some(index);
+        }
+    }
+    if let Some(word_start) = start {
+        tokens.push(&line[word_start..]);
+    }
+
+    tokens
+}
+
+fn is_code_line(line: &str) -> bool {
+    line.starts_with('\t') || line.starts_with("    ")
+}
+
+fn is_list_item(line: &str) -> bool {
+    list_marker_len(line.trim_start()) > 0
+}
+
+/// Split a line into its indentation and list marker, and the content after them.
+/// Lines without a list marker only have their indentation split off.
+pub fn split_list_marker(line: &str) -> (&str, &str) {
+    let rest = line.trim_start();
+    let marker = list_marker_len(rest);
+    let content = rest[marker..].trim_start();
+    line.split_at(line.len() - content.len())
+}
+
+/// Length of a `- `, `* `, `+ `, `1. ` or `1) ` marker at the start of a line
+fn list_marker_len(line: &str) -> usize {
+    if line.starts_with("- ") || line.starts_with("* ") || line.starts_with("+ ") {
+        return 2;
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") ")) {
+        digits + 2
+    } else {
+        0
+    }
+}
+
+/// Whether joining two wrapped lines needs a space (not between CJK characters)
+fn needs_space(previous: &str, next: &str) -> bool {
+    let wide = |c: Option<char>| c.and_then(|c| c.width()).unwrap_or(0) > 1;
+    !(wide(previous.chars().last()) && wide(next.chars().next()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_crlf_and_lone_cr() {
+        assert_eq!(normalize_newlines("a\r\nb\rc\nd"), "a\nb\nc\nd");
+    }
+
+    #[test]
+    fn detects_crlf_if_any_line_uses_it() {
+        assert_eq!(detect_line_ending("a\nb\r\nc"), "\r\n");
+        assert_eq!(detect_line_ending("a\nb"), "\n");
+        assert_eq!(detect_line_ending("a"), "\n");
+    }
+
+    #[test]
+    fn splits_bullets_and_numbers_with_their_indentation() {
+        assert_eq!(split_list_marker("- milk"), ("- ", "milk"));
+        assert_eq!(split_list_marker("  * eggs"), ("  * ", "eggs"));
+        assert_eq!(split_list_marker("+ flour"), ("+ ", "flour"));
+        assert_eq!(split_list_marker("12. step"), ("12. ", "step"));
+        assert_eq!(split_list_marker("\t3) step"), ("\t3) ", "step"));
+    }
+
+    #[test]
+    fn line_without_marker_only_loses_its_indentation() {
+        assert_eq!(split_list_marker("    plain text"), ("    ", "plain text"));
+        assert_eq!(split_list_marker("-not a marker"), ("", "-not a marker"));
+        assert_eq!(split_list_marker("3.14 is pi"), ("", "3.14 is pi"));
+    }
+
+    fn unwrap(text: &str) -> Vec<String> {
+        unwrap_lines(text, None, Segmenter::Simple)
+    }
+
+    #[test]
+    fn joins_wrapped_lines_until_a_sentence_ends() {
+        assert_eq!(
+            unwrap("This sentence was\nwrapped in two.\nThis one was not."),
+            ["This sentence was wrapped in two.", "This one was not."]
+        );
+    }
+
+    #[test]
+    fn keeps_blank_lines_lists_and_code_apart() {
+        assert_eq!(
+            unwrap("First part\n\nsecond part\n- item one\n- item\n    code line\nafter"),
+            [
+                "First part",
+                "",
+                "second part",
+                "- item one",
+                "- item",
+                "    code line",
+                "after"
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_cjk_lines_without_a_space() {
+        assert_eq!(unwrap("这是一个\n句子。"), ["这是一个句子。"]);
+    }
+
+    #[test]
+    fn smart_segmenter_joins_after_abbreviations() {
+        let text = "Ask Mr.\nSmith about it.";
+        assert_eq!(unwrap(text).len(), 2);
+        assert_eq!(
+            unwrap_lines(text, None, Segmenter::Smart),
+            ["Ask Mr. Smith about it."]
+        );
+    }
+
+    #[test]
+    fn rewraps_to_the_original_width() {
+        let original = "one two\nthree four";
+        let width = max_line_width(original);
+        assert_eq!(width, 10);
+        assert_eq!(
+            wrap_to_width("eins zwei drei vier fünf", width),
+            "eins zwei\ndrei vier\nfünf"
+        );
+        assert_eq!(wrap_to_width("一二三四五", 4), "一二\n三四\n五");
+    }
+
+    #[test]
+    fn knows_right_to_left_languages() {
+        assert!(is_rtl_language("ar"));
+        assert!(is_rtl_language("he-IL"));
+        assert!(is_rtl_language("fa"));
+        assert!(!is_rtl_language("en"));
+        assert!(!is_rtl_language("zh-tw"));
+    }
+
+    #[test]
+    fn embeds_every_non_blank_line() {
+        assert_eq!(
+            embed_rtl("مرحبا\n\nعالم"),
+            "\u{202B}مرحبا\u{202C}\n\n\u{202B}عالم\u{202C}"
+        );
+    }
+}
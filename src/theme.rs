@@ -0,0 +1,263 @@
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use colored::{Color, ColoredString, Colorize};
+use ratatui::style::{Color as TuiColor, Style};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Built-in color schemes selectable with `tzh config --theme`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Theme {
+    /// For dark terminal backgrounds
+    Dark,
+    /// For light terminal backgrounds
+    Light,
+}
+
+/// Color names for each kind of output, e.g. `blue` or `bright_white`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Colors {
+    pub status: String,
+    pub header: String,
+    pub title: String,
+    pub label: String,
+    pub translation: String,
+    pub error: String,
+    pub prompt: String,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self::preset(Theme::Dark)
+    }
+}
+
+impl Colors {
+    pub fn preset(theme: Theme) -> Self {
+        let (status, header, title, label, translation, error, prompt) = match theme {
+            Theme::Dark => (
+                "blue",
+                "green",
+                "blue",
+                "green",
+                "bright_white",
+                "red",
+                "green",
+            ),
+            Theme::Light => ("blue", "magenta", "blue", "magenta", "black", "red", "blue"),
+        };
+
+        Self {
+            status: status.to_string(),
+            header: header.to_string(),
+            title: title.to_string(),
+            label: label.to_string(),
+            translation: translation.to_string(),
+            error: error.to_string(),
+            prompt: prompt.to_string(),
+        }
+    }
+
+    /// Check that every role names a known color
+    pub fn validate(&self) -> Result<()> {
+        for (role, name) in self.roles() {
+            parse_color(name).map_err(|e| anyhow!("Invalid {} color: {}", role, e))?;
+        }
+        Ok(())
+    }
+
+    fn roles(&self) -> [(&'static str, &str); 7] {
+        [
+            ("status", &self.status),
+            ("header", &self.header),
+            ("title", &self.title),
+            ("label", &self.label),
+            ("translation", &self.translation),
+            ("error", &self.error),
+            ("prompt", &self.prompt),
+        ]
+    }
+}
+
+/// Parsed colors used for all terminal output
+struct Palette {
+    status: Color,
+    header: Color,
+    title: Color,
+    label: Color,
+    translation: Color,
+    error: Color,
+    prompt: Color,
+}
+
+static PALETTE: OnceLock<Palette> = OnceLock::new();
+
+/// Use the configured colors for the rest of the run. The colors must have been validated.
+pub fn init(colors: &Colors) {
+    let _ = PALETTE.set(palette(colors));
+}
+
+fn palette(colors: &Colors) -> Palette {
+    let color = |name: &str| parse_color(name).unwrap_or(Color::White);
+    Palette {
+        status: color(&colors.status),
+        header: color(&colors.header),
+        title: color(&colors.title),
+        label: color(&colors.label),
+        translation: color(&colors.translation),
+        error: color(&colors.error),
+        prompt: color(&colors.prompt),
+    }
+}
+
+fn current() -> &'static Palette {
+    PALETTE.get_or_init(|| palette(&Colors::default()))
+}
+
+fn parse_color(name: &str) -> Result<Color> {
+    name.replace('_', " ")
+        .parse()
+        .map_err(|_| anyhow!("unknown color {:?}", name))
+}
+
+/// Colors text by its role using the configured palette, instead of fixed colors
+pub trait Themed {
+    /// Progress and informational messages
+    fn status(&self) -> ColoredString;
+    /// Section headings such as `Original:`
+    fn header(&self) -> ColoredString;
+    /// Banners such as `Current Configuration:`
+    fn title(&self) -> ColoredString;
+    /// Names of settings and confirmations such as `Target language:`
+    fn label(&self) -> ColoredString;
+    fn translation(&self) -> ColoredString;
+    fn error(&self) -> ColoredString;
+    /// The interactive `tzh>` prompt
+    fn prompt(&self) -> ColoredString;
+}
+
+impl<T: AsRef<str> + ?Sized> Themed for T {
+    fn status(&self) -> ColoredString {
+        self.as_ref().color(current().status)
+    }
+
+    fn header(&self) -> ColoredString {
+        self.as_ref().color(current().header)
+    }
+
+    fn title(&self) -> ColoredString {
+        self.as_ref().color(current().title)
+    }
+
+    fn label(&self) -> ColoredString {
+        self.as_ref().color(current().label)
+    }
+
+    fn translation(&self) -> ColoredString {
+        self.as_ref().color(current().translation)
+    }
+
+    fn error(&self) -> ColoredString {
+        self.as_ref().color(current().error)
+    }
+
+    fn prompt(&self) -> ColoredString {
+        self.as_ref().color(current().prompt)
+    }
+}
+
+/// Roles colored in the split-pane interactive mode
+#[derive(Debug, Clone, Copy)]
+pub enum TuiRole {
+    Status,
+    Translation,
+    Error,
+}
+
+/// The configured color of a role as a ratatui style, unstyled when colors are off
+pub fn tui_style(role: TuiRole) -> Style {
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        return Style::new();
+    }
+
+    let palette = current();
+    let color = match role {
+        TuiRole::Status => palette.status,
+        TuiRole::Translation => palette.translation,
+        TuiRole::Error => palette.error,
+    };
+    Style::new().fg(tui_color(color))
+}
+
+/// ANSI white is light gray and bright white is white in ratatui's naming
+fn tui_color(color: Color) -> TuiColor {
+    match color {
+        Color::Black => TuiColor::Black,
+        Color::Red => TuiColor::Red,
+        Color::Green => TuiColor::Green,
+        Color::Yellow => TuiColor::Yellow,
+        Color::Blue => TuiColor::Blue,
+        Color::Magenta => TuiColor::Magenta,
+        Color::Cyan => TuiColor::Cyan,
+        Color::White => TuiColor::Gray,
+        Color::BrightBlack => TuiColor::DarkGray,
+        Color::BrightRed => TuiColor::LightRed,
+        Color::BrightGreen => TuiColor::LightGreen,
+        Color::BrightYellow => TuiColor::LightYellow,
+        Color::BrightBlue => TuiColor::LightBlue,
+        Color::BrightMagenta => TuiColor::LightMagenta,
+        Color::BrightCyan => TuiColor::LightCyan,
+        Color::BrightWhite => TuiColor::White,
+        Color::TrueColor { r, g, b } => TuiColor::Rgb(r, g, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dark_preset_keeps_the_original_colors() {
+        let colors = Colors::preset(Theme::Dark);
+        assert_eq!(colors.status, "blue");
+        assert_eq!(colors.header, "green");
+        assert_eq!(colors.title, "blue");
+        assert_eq!(colors.label, "green");
+        assert_eq!(colors.translation, "bright_white");
+        assert_eq!(colors.error, "red");
+        assert_eq!(colors.prompt, "green");
+    }
+
+    #[test]
+    fn presets_are_valid() {
+        Colors::preset(Theme::Dark).validate().unwrap();
+        Colors::preset(Theme::Light).validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_color() {
+        let colors = Colors {
+            label: "chartreuse".to_string(),
+            ..Colors::default()
+        };
+        let error = colors.validate().unwrap_err().to_string();
+        assert!(error.contains("label"), "{}", error);
+    }
+
+    #[test]
+    fn partial_colors_table_keeps_dark_defaults() {
+        let colors: Colors = toml::from_str("error = \"yellow\"").unwrap();
+        assert_eq!(colors.error, "yellow");
+        assert_eq!(colors.title, "blue");
+    }
+
+    #[test]
+    fn maps_colors_to_ratatui() {
+        let color = |name| tui_color(parse_color(name).unwrap());
+        assert_eq!(color("bright_white"), TuiColor::White);
+        assert_eq!(color("white"), TuiColor::Gray);
+        assert_eq!(color("bright_black"), TuiColor::DarkGray);
+        assert_eq!(color("blue"), TuiColor::Blue);
+    }
+}
@@ -0,0 +1,145 @@
+/// Approximate token count: about four characters per token for alphabetic
+/// scripts and one token per CJK character
+pub fn estimate_tokens(text: &str) -> usize {
+    let (wide, narrow) = text.chars().fold((0usize, 0usize), |(wide, narrow), c| {
+        if is_cjk(c) {
+            (wide + 1, narrow)
+        } else {
+            (wide, narrow + 1)
+        }
+    });
+
+    wide + narrow.div_ceil(4)
+}
+
+/// Completion tokens to request for translating `text` into `target_lang`.
+///
+/// The input estimate is scaled by how verbose the target language is compared
+/// to the source, plus a safety margin so translations are not cut off.
+pub fn estimate_max_tokens(text: &str, source_lang: Option<&str>, target_lang: &str) -> i32 {
+    const MARGIN: f64 = 1.5;
+    const MINIMUM: f64 = 32.0;
+
+    let estimate = expected_output_tokens(text, source_lang, target_lang) * MARGIN;
+
+    estimate.max(MINIMUM).ceil() as i32
+}
+
+/// Tokens the translation of `text` is expected to take, without any margin
+fn expected_output_tokens(text: &str, source_lang: Option<&str>, target_lang: &str) -> f64 {
+    let source_lang = source_lang.unwrap_or_else(|| guess_language(text));
+    let ratio = verbosity(target_lang) / verbosity(source_lang);
+    estimate_tokens(text) as f64 * ratio
+}
+
+/// System prompt and instruction tokens sent along with the text of every request
+const REQUEST_OVERHEAD: usize = 80;
+
+/// Size of a translation job, estimated without sending any request
+#[derive(Debug, Default)]
+pub struct JobEstimate {
+    pub segments: usize,
+    /// Segments passed through verbatim without a request
+    pub passthrough: usize,
+    pub requests: usize,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+}
+
+/// Estimate the requests and tokens needed to translate `segments`.
+///
+/// Blank segments and those matching `passthrough` need no request. Repeated
+/// segments are requested again, so they count towards requests and tokens.
+pub fn estimate_job(
+    segments: &[&str],
+    source_lang: Option<&str>,
+    target_lang: &str,
+    passthrough: impl Fn(&str) -> bool,
+) -> JobEstimate {
+    let mut estimate = JobEstimate {
+        segments: segments.iter().filter(|s| !s.is_empty()).count(),
+        ..JobEstimate::default()
+    };
+
+    let mut output_tokens = 0.0;
+    for segment in segments.iter().filter(|s| !s.is_empty()) {
+        if passthrough(segment) {
+            estimate.passthrough += 1;
+            continue;
+        }
+        estimate.requests += 1;
+        estimate.input_tokens += REQUEST_OVERHEAD + estimate_tokens(segment);
+        output_tokens += expected_output_tokens(segment, source_lang, target_lang);
+    }
+    estimate.output_tokens = output_tokens.ceil() as usize;
+
+    estimate
+}
+
+/// Tokens needed to express the same content, relative to English
+fn verbosity(lang: &str) -> f64 {
+    match lang {
+        "zh" | "zh-cn" | "zh-tw" => 0.7,
+        "ja" => 0.8,
+        "ko" => 0.9,
+        "de" | "ru" => 1.3,
+        "fr" | "es" | "it" | "pt" => 1.2,
+        "ar" | "hi" | "th" | "vi" => 1.4,
+        _ => 1.0,
+    }
+}
+
+/// Treat mostly CJK text as Chinese and everything else as English
+fn guess_language(text: &str) -> &'static str {
+    let cjk = text.chars().filter(|c| is_cjk(*c)).count();
+    let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+    if cjk * 2 > letters { "zh" } else { "en" }
+}
+
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' // Hiragana and Katakana
+        | '\u{3400}'..='\u{4dbf}' // CJK Extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK Unified Ideographs
+        | '\u{ac00}'..='\u{d7af}' // Hangul syllables
+        | '\u{f900}'..='\u{faff}' // CJK Compatibility Ideographs
+        | '\u{ff00}'..='\u{ffef}' // Fullwidth forms
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_four_characters_or_one_cjk_character_per_token() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("Hello"), 2);
+        assert_eq!(estimate_tokens("你好"), 2);
+        assert_eq!(estimate_tokens("你好 world"), 4);
+    }
+
+    #[test]
+    fn job_estimate_counts_repeats_and_skips_blanks_and_passthrough() {
+        let segments = ["Hello world", "", "https://example.com", "Hello world"];
+        let estimate = estimate_job(&segments, Some("en"), "zh", |segment| {
+            segment.starts_with("https://")
+        });
+
+        assert_eq!(estimate.segments, 3);
+        assert_eq!(estimate.passthrough, 1);
+        assert_eq!(estimate.requests, 2);
+        assert_eq!(estimate.input_tokens, 2 * (REQUEST_OVERHEAD + 3));
+        // 3 tokens each, at 0.7 for Chinese
+        assert_eq!(estimate.output_tokens, 5);
+    }
+
+    #[test]
+    fn max_tokens_has_a_floor() {
+        assert_eq!(estimate_max_tokens("Hi", Some("en"), "de"), 32);
+        assert_eq!(
+            estimate_max_tokens(&"word ".repeat(100), Some("en"), "de"),
+            244
+        );
+    }
+}
@@ -1,9 +1,29 @@
 use anyhow::{Context, Result, anyhow};
-use reqwest::Client;
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+use regex::Regex;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, RequestBuilder, Url};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use uuid::Uuid;
 
-use crate::config::Config;
+use crate::audit::AuditLog;
+use crate::backend::{ApiError, Backend, EmptyResponse, TextKind};
+use crate::card::{self, WordCard};
+use crate::clock::{Sleeper, TokioSleeper};
+use crate::config::{BackendKind, Config, Pricing};
+use crate::debug_bundle::{self, DebugBundle};
+use crate::deepl::DeepLBackend;
+use crate::history::History;
+use crate::output;
+use crate::placeholders::{self, Protected};
+use crate::preset::Preset;
+use crate::sse::EventBuffer;
+use crate::text;
+use crate::tokens;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct ChatMessage {
@@ -11,39 +31,507 @@ struct ChatMessage {
     content: String,
 }
 
+/// Message as sent in a request, where content may be split into blocks
+#[derive(Debug, Serialize)]
+struct RequestMessage {
+    role: String,
+    content: MessageContent,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum MessageContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+#[derive(Debug, Serialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_control: Option<CacheControl>,
+}
+
+#[derive(Debug, Serialize)]
+struct CacheControl {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl RequestMessage {
+    /// Convert a chat message, marking system prompts as cacheable if requested
+    fn from_chat(message: ChatMessage, prompt_caching: bool) -> Self {
+        let content = if prompt_caching && message.role == "system" {
+            MessageContent::Blocks(vec![ContentBlock {
+                kind: "text".to_string(),
+                text: message.content,
+                cache_control: Some(CacheControl {
+                    kind: "ephemeral".to_string(),
+                }),
+            }])
+        } else {
+            MessageContent::Text(message.content)
+        };
+
+        Self {
+            role: message.role,
+            content,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct ChatRequest {
     model: String,
-    messages: Vec<ChatMessage>,
+    messages: Vec<RequestMessage>,
     temperature: f32,
     max_tokens: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+/// Streaming settings, asking for the token usage in a final chunk
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    include_usage: bool,
 }
 
+/// A response choice, accepting both chat-style `message.content` and completion-style `text`
 #[derive(Debug, Deserialize)]
 struct ChatChoice {
-    message: ChatMessage,
+    message: Option<ResponseMessage>,
+    text: Option<String>,
+    logprobs: Option<ChoiceLogprobs>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseMessage {
+    content: Option<String>,
+    /// Chain of thought returned separately by reasoning models such as `deepseek-reasoner`
+    reasoning_content: Option<String>,
+}
+
+impl ChatChoice {
+    /// Generated text, preferring `message.content` over `text` when both are present
+    fn content(&self) -> Option<&str> {
+        self.message
+            .as_ref()
+            .and_then(|message| message.content.as_deref())
+            .or(self.text.as_deref())
+    }
+
+    fn reasoning(&self) -> Option<&str> {
+        self.message
+            .as_ref()
+            .and_then(|message| message.reasoning_content.as_deref())
+            .map(str::trim)
+            .filter(|reasoning| !reasoning.is_empty())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChoiceLogprobs {
+    content: Option<Vec<TokenLogprob>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenLogprob {
+    logprob: f64,
 }
 
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<ChatChoice>,
+    usage: Option<Usage>,
+}
+
+/// One event of a streamed response, carrying the next piece of the answer
+#[derive(Debug, Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChoice {
+    delta: Option<StreamDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamDelta {
+    content: Option<String>,
+    reasoning_content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Usage {
+    prompt_tokens: Option<usize>,
+    completion_tokens: Option<usize>,
+    completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionTokensDetails {
+    reasoning_tokens: Option<u64>,
+}
+
+/// A translated text together with optional metadata reported by the endpoint
+#[derive(Debug, Clone, Default)]
+pub struct Translation {
+    pub text: String,
+    /// Average token probability (0.0 to 1.0), when the endpoint returned logprobs
+    pub confidence: Option<f64>,
+    /// Reasoning the model returned along with the answer
+    pub reasoning: Option<String>,
+    /// Tokens spent on reasoning, when the endpoint reported them
+    pub reasoning_tokens: Option<u64>,
+    /// Prompt tokens of the answering request, when the endpoint reported them
+    pub input_tokens: Option<usize>,
+    /// Completion tokens of the answering request, when the endpoint reported them
+    pub output_tokens: Option<usize>,
+}
+
+impl Translation {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Spending cap for a run, tracked from the token usage of each response
+struct CostLimit {
+    pricing: Pricing,
+    max_cost: f64,
+    spent: Mutex<f64>,
+}
+
+/// The run has spent its maximum cost, so no further requests are sent
+#[derive(Debug)]
+pub struct CostLimitReached {
+    max_cost: f64,
 }
 
+impl fmt::Display for CostLimitReached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Cost limit of {:.4} per run reached, no further requests are sent",
+            self.max_cost
+        )
+    }
+}
+
+impl std::error::Error for CostLimitReached {}
+
+/// Endpoint, model and credentials a single request is sent to
+struct Provider<'a> {
+    endpoint: &'a str,
+    model: &'a str,
+    api_key: Option<&'a str>,
+}
+
+/// Hosts whose chat APIs accept `cache_control` markers; others may reject them
+const CACHE_MARKER_HOSTS: [&str; 2] = ["anthropic.com", "openrouter.ai"];
+
+impl Provider<'_> {
+    /// Whether the system prompt may be marked as cacheable in requests to this provider
+    fn supports_cache_markers(&self) -> bool {
+        let Some(host) = Url::parse(self.endpoint)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        else {
+            return false;
+        };
+        CACHE_MARKER_HOSTS
+            .iter()
+            .any(|known| host == *known || host.ends_with(&format!(".{}", known)))
+    }
+}
+
+/// A provider given on the command line as `endpoint:model`
+#[derive(Debug, Clone)]
+pub struct ProviderSpec {
+    pub endpoint: String,
+    pub model: String,
+    pub api_key: Option<String>,
+}
+
+impl ProviderSpec {
+    /// Parse `endpoint:model`, where the endpoint may have a port and the model may contain `:`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("expected `endpoint:model`, got {:?}", spec);
+        let (scheme, rest) = spec.split_once("://").ok_or_else(invalid)?;
+
+        // Skip the host and port, then the model starts at the first `:` after the path
+        let host_end = rest.find([':', '/']).unwrap_or(rest.len());
+        let mut end = host_end;
+        if rest[end..].starts_with(':') {
+            let port = rest[end + 1..]
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len() - end - 1);
+            end += 1 + port;
+        }
+        let separator = rest[end..].find(':').map(|offset| end + offset);
+
+        match separator {
+            Some(separator) if separator + 1 < rest.len() && host_end > 0 => Ok(Self {
+                endpoint: format!("{}://{}", scheme, rest[..separator].trim_end_matches('/')),
+                model: rest[separator + 1..].to_string(),
+                api_key: None,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} @ {}", self.model, self.endpoint)
+    }
+}
+
+/// The chat completion API of an OpenAI-compatible provider
+struct ChatBackend<'a> {
+    translator: &'a Translator,
+    provider: Provider<'a>,
+    /// Demand a non-empty answer after the model returned nothing
+    insist: bool,
+}
+
+impl Backend for ChatBackend<'_> {
+    async fn translate(
+        &self,
+        text: &str,
+        kind: TextKind,
+        target_lang: &str,
+        source_lang: Option<&str>,
+        idempotency_key: &str,
+    ) -> Result<Translation> {
+        self.translator
+            .translate_attempt(
+                &self.provider,
+                text,
+                kind,
+                target_lang,
+                source_lang,
+                idempotency_key,
+                self.insist,
+            )
+            .await
+    }
+
+    fn can_insist(&self) -> bool {
+        true
+    }
+}
+
+/// Immediate retries after the model answers with an empty translation
+const MAX_EMPTY_RETRIES: usize = 2;
+
+/// Retries after a translation dropped a protected placeholder
+const MAX_PLACEHOLDER_RETRIES: usize = 2;
+
+/// Longest wait between retries while a provider is overloaded, unless the base delay is longer
+const MAX_OVERLOAD_DELAY: Duration = Duration::from_secs(60);
+
+/// Built-in system prompt for translating text
+const LINE_SYSTEM_PROMPT: &str = "You are a professional translator. Rules:
+- Output ONLY the translated text
+- No explanations, notes, or additional content
+- No quotes around the translation
+- For empty input, output nothing
+- Maintain the same formatting and line structure as the original";
+
+/// Built-in system prompt for looking up words
+const WORD_SYSTEM_PROMPT: &str = "You are a professional translator. Rules:
+- Output ONLY the translated word/phrase
+- No pronunciation guides (no pinyin, romanization, or phonetic notation)
+- No parentheses or brackets
+- No explanations or additional text
+- No quotes around translation
+- Output all common and distinct meanings of the word, separated by semicolons or commas
+- For empty input, output nothing";
+
+/// Appended to the prompt when retrying after an empty answer
+const INSIST_PROMPT: &str =
+    "Your previous reply was empty. Reply with the translation only; it must not be empty.";
+
 pub struct Translator {
     client: Client,
+    sleeper: Box<dyn Sleeper>,
     config: Config,
+    deepl: Option<DeepLBackend>,
+    answer_extractor: Option<Regex>,
+    verbose: bool,
+    translate_all: bool,
+    with_confidence: bool,
+    auto_max_tokens: bool,
+    preserve_lists: bool,
+    /// Swap format placeholders for sentinels the model is told to keep
+    protect_placeholders: bool,
+    /// Likely source language, which the model may override unlike a hard source language
+    source_hint: Option<String>,
+    /// Prompt style replacing the built-in prompts and temperature
+    preset: Option<Preset>,
+    /// Ask for a definition in the word's own language along with word translations
+    bidirectional: bool,
+    /// Earlier turns sent as context, if session history is enabled
+    history: Option<Mutex<History>>,
+    debug_bundle: Option<Arc<DebugBundle>>,
+    audit_log: Option<Arc<AuditLog>>,
+    cost_limit: Option<CostLimit>,
 }
 
 impl Translator {
     pub fn new(config: &Config) -> Self {
+        // Decompression also makes the client send a matching Accept-Encoding header
         let client = Client::builder()
             .timeout(Duration::from_secs(config.timeout()))
+            .gzip(config.compression())
+            .brotli(config.compression())
+            .deflate(config.compression())
             .build()
             .expect("Failed to create HTTP client");
 
+        Self::with_client(config, client)
+    }
+
+    /// Create a translator that sends requests through a pre-configured client
+    pub fn with_client(config: &Config, client: Client) -> Self {
+        Self::with_parts(config, client, Box::new(TokioSleeper))
+    }
+
+    /// Create a translator that also waits between retries with the given sleeper
+    pub fn with_parts(config: &Config, client: Client, sleeper: Box<dyn Sleeper>) -> Self {
+        // The pattern has already been validated when loading the config
+        let answer_extractor = config
+            .answer_extractor()
+            .map(|pattern| Regex::new(pattern).expect("Invalid answer_extractor regex"));
+
+        let deepl = match config.backend() {
+            BackendKind::Chat => None,
+            BackendKind::Deepl => Some(DeepLBackend::new(client.clone(), config)),
+        };
+
         Self {
             client,
+            sleeper,
             config: config.clone(),
+            deepl,
+            answer_extractor,
+            verbose: false,
+            translate_all: false,
+            with_confidence: false,
+            auto_max_tokens: false,
+            preserve_lists: false,
+            protect_placeholders: false,
+            source_hint: None,
+            preset: None,
+            bidirectional: false,
+            history: None,
+            debug_bundle: None,
+            audit_log: None,
+            cost_limit: None,
+        }
+    }
+
+    /// The HTTP client requests are sent with, for other downloads in the same run
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// Send every line to the model, even numbers, URLs and code identifiers
+    pub fn set_translate_all(&mut self, translate_all: bool) {
+        self.translate_all = translate_all;
+    }
+
+    /// Request logprobs so a confidence score can be reported
+    pub fn set_with_confidence(&mut self, with_confidence: bool) {
+        self.with_confidence = with_confidence;
+    }
+
+    /// Size max tokens for each request from its input instead of using the config value
+    pub fn set_auto_max_tokens(&mut self, auto_max_tokens: bool) {
+        self.auto_max_tokens = auto_max_tokens;
+    }
+
+    /// Keep the indentation and list marker of each line out of the request
+    pub fn set_preserve_lists(&mut self, preserve_lists: bool) {
+        self.preserve_lists = preserve_lists;
+    }
+
+    /// Keep format placeholders such as `{0}`, `%s` and `{{name}}` verbatim
+    pub fn set_protect_placeholders(&mut self, protect_placeholders: bool) {
+        self.protect_placeholders = protect_placeholders;
+    }
+
+    /// Suggest a likely source language for requests that don't assert one
+    pub fn set_source_hint(&mut self, source_hint: Option<String>) {
+        self.source_hint = source_hint;
+    }
+
+    pub fn set_preset(&mut self, preset: Option<Preset>) {
+        self.preset = preset;
+    }
+
+    pub fn set_bidirectional(&mut self, bidirectional: bool) {
+        self.bidirectional = bidirectional;
+    }
+
+    /// Send up to `max_turns` earlier turns along with each request
+    pub fn set_history(&mut self, max_turns: usize) {
+        self.history = Some(Mutex::new(History::new(max_turns)));
+    }
+
+    /// Continue the session history, audit log and preset of a translator this one replaces
+    pub fn inherit_session(&mut self, previous: &Translator) {
+        self.preset = previous.preset.clone();
+        self.history = previous
+            .history
+            .as_ref()
+            .map(|history| Mutex::new(history.lock().unwrap().clone()));
+        self.audit_log = previous.audit_log.clone();
+    }
+
+    /// Stop sending requests once the reported token usage costs `max_cost` or more
+    pub fn set_max_cost(&mut self, pricing: Pricing, max_cost: f64) {
+        self.cost_limit = Some(CostLimit {
+            pricing,
+            max_cost,
+            spent: Mutex::new(0.0),
+        });
+    }
+
+    /// Log every request sent to the audit log
+    pub fn set_audit_log(&mut self, audit_log: Arc<AuditLog>) {
+        self.audit_log = Some(audit_log);
+    }
+
+    /// Record every request and its outcome in a debug bundle
+    pub fn set_debug_bundle(&mut self, bundle: Arc<DebugBundle>) {
+        if let Some(deepl) = &mut self.deepl {
+            deepl.set_debug_bundle(bundle.clone());
+        }
+        self.debug_bundle = Some(bundle);
+    }
+
+    /// Forget all earlier turns of the session
+    pub fn clear_history(&self) {
+        if let Some(history) = &self.history {
+            history.lock().unwrap().clear();
         }
     }
 
@@ -55,55 +543,202 @@ impl Translator {
         mut callback: F,
     ) -> Result<()>
     where
-        F: FnMut(&str, &str),
+        F: FnMut(&str, &Translation),
     {
         if text.is_empty() {
-            callback(text, "");
+            callback(text, &Translation::default());
             return Ok(());
         }
 
-        let max_retries = 3;
-        let mut last_error = None;
+        // Every line of a longer text keeps its own marker
+        if self.preserve_lists && text.contains('\n') {
+            let result = self.translate_list(text, target_lang, source_lang).await?;
+            callback(text, &result);
+            return Ok(());
+        }
 
-        for attempt in 1..=max_retries {
-            match self
-                .translate_line_attempt(text, target_lang, source_lang)
-                .await
-            {
-                Ok(result) => {
-                    callback(text, &result);
-                    return Ok(());
-                }
-                Err(e) => {
-                    last_error = Some(e);
-                    if attempt < max_retries {
-                        let delay = Duration::from_millis(1000 * attempt);
-                        tokio::time::sleep(delay).await;
-                    }
-                }
-            }
+        // Only the content after a list marker is sent, the marker is restored verbatim
+        let (prefix, content) = if self.preserve_lists {
+            text::split_list_marker(text)
+        } else {
+            ("", text)
+        };
+
+        // Lines with nothing to translate are passed through verbatim
+        if content.is_empty() || (!self.translate_all && is_untranslatable(content)) {
+            callback(text, &Translation::new(text));
+            return Ok(());
+        }
+
+        let mut result = self
+            .translate_text(content, TextKind::Line, target_lang, source_lang)
+            .await?;
+        if !prefix.is_empty() {
+            result.text = format!("{}{}", prefix, result.text);
+        }
+        callback(text, &result);
+        Ok(())
+    }
+
+    /// Translate the lines of a text in one request with their indentation and list
+    /// markers taken out, putting each marker back on its line. If the translation
+    /// doesn't keep the lines apart, the lines are translated one by one instead.
+    async fn translate_list(
+        &self,
+        text: &str,
+        target_lang: &str,
+        source_lang: Option<&str>,
+    ) -> Result<Translation> {
+        let lines: Vec<(&str, &str)> = text.lines().map(text::split_list_marker).collect();
+        let contents: Vec<&str> = lines.iter().map(|(_, content)| *content).collect();
+        let passed_through = |content: &str| {
+            content.is_empty() || (!self.translate_all && is_untranslatable(content))
+        };
+        if contents.iter().all(|content| passed_through(content)) {
+            return Ok(Translation::new(text));
+        }
+
+        let mut translation = self
+            .translate_text(
+                &contents.join("\n"),
+                TextKind::Line,
+                target_lang,
+                source_lang,
+            )
+            .await?;
+        let translated: Vec<&str> = translation.text.lines().collect();
+        if translated.len() == lines.len() {
+            translation.text = lines
+                .iter()
+                .zip(translated)
+                .map(|((prefix, _), line)| format!("{}{}", prefix, line.trim()))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Ok(translation);
         }
 
-        Err(last_error.unwrap())
+        let mut output = Vec::new();
+        for (prefix, content) in lines {
+            let line = if passed_through(content) {
+                content.to_string()
+            } else {
+                self.translate_text(content, TextKind::Line, target_lang, source_lang)
+                    .await?
+                    .text
+            };
+            output.push(format!("{}{}", prefix, line));
+        }
+        Ok(Translation::new(&output.join("\n")))
+    }
+
+    /// Translate several lines with up to `concurrency` requests in flight.
+    /// Results are returned in input order and fail independently.
+    ///
+    /// Each result is the whole `Translation` rather than only its `text`, so the
+    /// reported usage and confidence of every item stay available.
+    pub async fn translate_batch(
+        &self,
+        items: &[&str],
+        target_lang: &str,
+        source_lang: Option<&str>,
+        concurrency: usize,
+    ) -> Vec<Result<Translation>> {
+        let lines = stream::iter(items.iter().map(|item| item.to_string()));
+        self.translate_stream(lines, target_lang, source_lang, concurrency)
+            .map(|(_, result)| result)
+            .collect()
+            .await
+    }
+
+    /// Translate lines as they arrive with up to `concurrency` requests in flight,
+    /// yielding each line with its result in input order.
+    ///
+    /// The original comes with failures too, as `(line, Err(_))`, so a failed line
+    /// can be reported and the stream goes on with the next one.
+    pub fn translate_stream<'a>(
+        &'a self,
+        lines: impl Stream<Item = String> + 'a,
+        target_lang: &'a str,
+        source_lang: Option<&'a str>,
+        concurrency: usize,
+    ) -> impl Stream<Item = (String, Result<Translation>)> + 'a {
+        lines
+            .map(move |line| async move {
+                let mut translation = Translation::default();
+                let result = self
+                    .translate_line(&line, target_lang, source_lang, |_, result| {
+                        translation = result.clone();
+                    })
+                    .await;
+                (line, result.map(|()| translation))
+            })
+            .buffered(concurrency.max(1))
+    }
+
+    /// Translate `text` with every provider at once, to compare their candidates.
+    /// Each provider gets a single attempt and results are in the order of `providers`.
+    pub async fn translate_consensus(
+        &self,
+        text: &str,
+        providers: &[ProviderSpec],
+        target_lang: &str,
+        source_lang: Option<&str>,
+    ) -> Vec<Result<Translation>> {
+        let requests = providers.iter().map(|spec| async move {
+            let backend = ChatBackend {
+                translator: self,
+                provider: Provider {
+                    endpoint: &spec.endpoint,
+                    model: &spec.model,
+                    api_key: spec.api_key.as_deref(),
+                },
+                insist: false,
+            };
+            self.audit(&spec.model, text, target_lang, source_lang);
+            let idempotency_key = Uuid::new_v4().to_string();
+            backend
+                .translate(
+                    text,
+                    TextKind::Line,
+                    target_lang,
+                    source_lang,
+                    &idempotency_key,
+                )
+                .await
+        });
+        future::join_all(requests).await
     }
 
-    async fn translate_line_attempt(
+    /// Ask the configured model which candidate translates `text` most accurately,
+    /// returning the index of that candidate
+    pub async fn judge(
         &self,
         text: &str,
+        candidates: &[&str],
         target_lang: &str,
         source_lang: Option<&str>,
-    ) -> Result<String> {
-        let prompt = self.build_line_translation_prompt(text, target_lang, source_lang);
+    ) -> Result<usize> {
+        let source = match source_lang {
+            Some(source) => format!(" (in {})", self.lang_code_to_name(source)),
+            None => String::new(),
+        };
+        let mut prompt = format!(
+            "Source text{}:\n{}\n\nCandidate translations into {}:\n",
+            source,
+            text,
+            self.lang_code_to_name(target_lang)
+        );
+        for (index, candidate) in candidates.iter().enumerate() {
+            prompt.push_str(&format!("{}. {}\n", index + 1, candidate));
+        }
+        prompt.push_str(
+            "\nWhich of these is the most accurate translation? Reply with its number only.",
+        );
 
         let messages = vec![
             ChatMessage {
                 role: "system".to_string(),
-                content: "You are a professional translator. Rules:
-- Output ONLY the translated text
-- No explanations, notes, or additional content
-- No quotes around the translation
-- For empty input, output nothing
-- Maintain the same formatting and line structure as the original"
+                content: "You are an expert translation reviewer. Reply with a single number."
                     .to_string(),
             },
             ChatMessage {
@@ -111,176 +746,696 @@ impl Translator {
                 content: prompt,
             },
         ];
+        self.audit(self.config.model(), text, target_lang, source_lang);
+        let reply = self
+            .send_chat_request(
+                &self.primary_provider(),
+                messages,
+                self.config.max_tokens(),
+                &Uuid::new_v4().to_string(),
+            )
+            .await?;
 
-        let request = ChatRequest {
-            model: self.config.model().to_string(),
-            messages,
-            temperature: self.config.temperature(),
-            max_tokens: self.config.max_tokens(),
-        };
-
-        let url = format!("{}/chat/completions", self.config.endpoint());
-        let mut req_builder = self.client.post(&url).json(&request);
-
-        // Add authorization header if API key is available
-        if let Some(api_key) = self.config.api_key() {
-            if !api_key.is_empty() {
-                req_builder = req_builder.header("Authorization", format!("Bearer {}", api_key));
-            }
+        let number: String = reply
+            .text
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        match number.parse::<usize>() {
+            Ok(choice) if (1..=candidates.len()).contains(&choice) => Ok(choice - 1),
+            _ => Err(anyhow!(
+                "Judge reply {:?} does not name a candidate",
+                reply.text
+            )),
         }
+    }
 
-        let response = req_builder
-            .send()
-            .await
-            .context("Failed to send translation request")?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow!(
-                "API request failed with status {}: {}",
-                status,
-                error_text
-            ));
+    pub async fn translate_word<F>(
+        &self,
+        word: &str,
+        target_lang: &str,
+        source_lang: Option<&str>,
+        mut callback: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&str, &Translation),
+    {
+        // Numbers, URLs and code identifiers are passed through as with lines
+        if !self.translate_all && is_untranslatable(word) {
+            callback(word, &Translation::new(word));
+            return Ok(());
         }
 
-        let chat_response: ChatResponse = response
-            .json()
-            .await
-            .context("Failed to parse API response")?;
+        let translation = self
+            .translate_text(word, TextKind::Word, target_lang, source_lang)
+            .await?;
+        callback(word, &translation);
+        Ok(())
+    }
 
-        if chat_response.choices.is_empty() {
-            return Err(anyhow!("No translation choices returned from API"));
+    /// Look up a word as a flashcard with its translation and a definition; needs
+    /// `set_bidirectional` to ask for the definition
+    pub async fn translate_card(
+        &self,
+        word: &str,
+        target_lang: &str,
+        source_lang: Option<&str>,
+    ) -> Result<WordCard> {
+        if !self.translate_all && is_untranslatable(word) {
+            return Ok(WordCard::parse(word, word));
         }
 
-        let translated_text = chat_response.choices[0].message.content.trim();
-
-        // Remove quotes if the response is wrapped in them
-        let cleaned_text = if (translated_text.starts_with('"') && translated_text.ends_with('"'))
-            || (translated_text.starts_with('\'') && translated_text.ends_with('\''))
-        {
-            &translated_text[1..translated_text.len() - 1]
-        } else {
-            translated_text
-        };
-
-        Ok(cleaned_text.to_string())
+        let reply = self
+            .translate_text(word, TextKind::Word, target_lang, source_lang)
+            .await?;
+        Ok(WordCard::parse(word, &reply.text))
     }
 
-    pub async fn translate_word<F>(
+    /// Translate like `translate_line` or `translate_word`, passing the answer received
+    /// so far to `on_partial` while it streams in.
+    ///
+    /// Only the chat backend streams, and only when nothing has to be checked or
+    /// restored in the whole answer, such as protected placeholders. If the streamed
+    /// request fails, the text is translated again with the usual retries.
+    pub async fn translate_live<F>(
         &self,
-        word: &str,
+        text: &str,
+        kind: TextKind,
         target_lang: &str,
         source_lang: Option<&str>,
-        mut callback: F,
-    ) -> Result<()>
+        on_partial: F,
+    ) -> Result<Translation>
     where
-        F: FnMut(&str, &str),
+        F: FnMut(&str),
     {
-        let max_retries = 3;
-        let mut last_error = None;
-
-        for attempt in 1..=max_retries {
-            match self
-                .translate_word_attempt(word, target_lang, source_lang)
-                .await
-            {
-                Ok(translation) => {
-                    callback(word, &translation);
-                    return Ok(());
+        let streamable = self.deepl.is_none()
+            && !self.protect_placeholders
+            && !self.preserve_lists
+            && !self.with_confidence
+            && !self.bidirectional
+            && self.debug_bundle.is_none()
+            && !text.is_empty()
+            && (self.translate_all || !is_untranslatable(text));
+        if !streamable {
+            let mut output = Translation::default();
+            let callback = |_original: &str, translation: &Translation| {
+                output = translation.clone();
+            };
+            match kind {
+                TextKind::Line => {
+                    self.translate_line(text, target_lang, source_lang, callback)
+                        .await?
                 }
-                Err(e) => {
-                    last_error = Some(e);
-                    if attempt < max_retries {
-                        let delay = Duration::from_millis(1000 * attempt);
-                        tokio::time::sleep(delay).await;
-                    }
+                TextKind::Word => {
+                    self.translate_word(text, target_lang, source_lang, callback)
+                        .await?
                 }
             }
+            return Ok(output);
         }
 
-        Err(last_error.unwrap())
+        self.check_cost_limit()?;
+        let provider = self.primary_provider();
+        self.audit(provider.model, text, target_lang, source_lang);
+        let (prompt, messages) = self.chat_messages(text, kind, target_lang, source_lang, false);
+        let max_tokens = self.max_tokens_for(text, target_lang, source_lang);
+        let streamed = self
+            .stream_chat_request(
+                &provider,
+                messages,
+                max_tokens,
+                &Uuid::new_v4().to_string(),
+                on_partial,
+            )
+            .await;
+
+        match streamed {
+            Ok(translation) => {
+                self.record_history(&prompt, &translation);
+                Ok(translation)
+            }
+            Err(e) => {
+                if self.verbose {
+                    output::notice(&format!("{}, retrying", e));
+                }
+                self.translate_text(text, kind, target_lang, source_lang)
+                    .await
+            }
+        }
     }
 
-    async fn translate_word_attempt(
+    /// Translate with placeholders protected if enabled, asking again when one goes missing
+    async fn translate_text(
         &self,
-        word: &str,
+        text: &str,
+        kind: TextKind,
         target_lang: &str,
         source_lang: Option<&str>,
-    ) -> Result<String> {
-        let prompt = self.build_word_translation_prompt(word, target_lang, source_lang);
+    ) -> Result<Translation> {
+        let protected = self
+            .protect_placeholders
+            .then(|| Protected::new(text))
+            .flatten();
+        let Some(protected) = protected else {
+            return self
+                .translate_with_retries(text, kind, target_lang, source_lang)
+                .await;
+        };
 
-        let messages = vec![
-            ChatMessage {
-                role: "system".to_string(),
-                content: "You are a professional translator. Rules:
-- Output ONLY the translated word/phrase
-- No pronunciation guides (no pinyin, romanization, or phonetic notation)
-- No parentheses or brackets
-- No explanations or additional text
-- No quotes around translation
-- Output all common and distinct meanings of the word, separated by semicolons or commas
-- For empty input, output nothing".to_string(),
-            },
-            ChatMessage {
-                role: "user".to_string(),
-                content: prompt,
-            },
-        ];
+        let mut losses = 0;
+        loop {
+            let mut translation = self
+                .translate_with_retries(protected.text(), kind, target_lang, source_lang)
+                .await?;
+            match protected.restore(&translation.text) {
+                Ok(restored) => {
+                    translation.text = restored;
+                    return Ok(translation);
+                }
+                Err(e) if losses < MAX_PLACEHOLDER_RETRIES => {
+                    losses += 1;
+                    if self.verbose {
+                        output::notice(&format!("{}, retrying", e));
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
 
-        let request = ChatRequest {
-            model: self.config.model().to_string(),
-            messages,
-            temperature: self.config.temperature(),
-            max_tokens: self.config.max_tokens(),
+    /// Translate with the configured backend, retrying and then trying the fallback
+    async fn translate_with_retries(
+        &self,
+        text: &str,
+        kind: TextKind,
+        target_lang: &str,
+        source_lang: Option<&str>,
+    ) -> Result<Translation> {
+        self.check_cost_limit()?;
+
+        let max_retries = 3;
+        let mut attempt = 1;
+        let mut empty_responses = 0;
+        let mut overloads = 0;
+
+        // Reuse the same key across retries so the endpoint can dedupe them
+        let mut idempotency_key = Uuid::new_v4().to_string();
+        let mut chat = ChatBackend {
+            translator: self,
+            provider: self.primary_provider(),
+            insist: false,
         };
 
-        let url = format!("{}/chat/completions", self.config.endpoint());
-        let mut req_builder = self.client.post(&url).json(&request);
+        let last_error = loop {
+            let result = match &self.deepl {
+                Some(deepl) => {
+                    self.audit("deepl", text, target_lang, source_lang);
+                    deepl
+                        .translate(text, kind, target_lang, source_lang, &idempotency_key)
+                        .await
+                }
+                None => {
+                    self.audit(chat.provider.model, text, target_lang, source_lang);
+                    chat.translate(text, kind, target_lang, source_lang, &idempotency_key)
+                        .await
+                }
+            };
 
-        // Add authorization header if API key is available
-        if let Some(api_key) = self.config.api_key() {
-            if !api_key.is_empty() {
-                req_builder = req_builder.header("Authorization", format!("Bearer {}", api_key));
+            match result {
+                Ok(translation) => return Ok(translation),
+                // Asking again with the same prompt tends to give nothing again,
+                // so empty answers are retried right away with a firmer prompt
+                Err(e) if e.is::<EmptyResponse>() => {
+                    let can_insist = match &self.deepl {
+                        Some(deepl) => deepl.can_insist(),
+                        None => chat.can_insist(),
+                    };
+                    if !can_insist {
+                        break e;
+                    }
+                    empty_responses += 1;
+                    if empty_responses > MAX_EMPTY_RETRIES {
+                        break anyhow!(
+                            "Model returned an empty translation {} times in a row",
+                            empty_responses
+                        );
+                    }
+                    chat.insist = true;
+                    // The prompt changes, so this is a new request
+                    idempotency_key = Uuid::new_v4().to_string();
+                }
+                // A busy provider needs longer to recover than a flaky connection
+                Err(e)
+                    if e.downcast_ref::<ApiError>()
+                        .is_some_and(ApiError::is_overloaded) =>
+                {
+                    if overloads == self.config.overload_max_retries() {
+                        break e;
+                    }
+                    let delay = self.overload_delay(overloads);
+                    if self.verbose {
+                        output::notice(&format!("Provider overloaded, retrying in {:?}", delay));
+                    }
+                    self.sleeper.sleep(delay).await;
+                    overloads += 1;
+                }
+                Err(e) => {
+                    if attempt == max_retries {
+                        break e;
+                    }
+                    let delay = Duration::from_secs(1 << (attempt - 1));
+                    self.sleeper.sleep(delay).await;
+                    attempt += 1;
+                }
             }
+        };
+
+        // Give the fallback provider a single chance before giving up
+        if let Some(provider) = self.fallback_provider() {
+            self.log_fallback(provider.endpoint, provider.model);
+            self.audit(provider.model, text, target_lang, source_lang);
+            let fallback = ChatBackend {
+                translator: self,
+                provider,
+                insist: chat.insist,
+            };
+            // Another provider, so its key must not be taken for a retry of the first
+            let idempotency_key = Uuid::new_v4().to_string();
+            return fallback
+                .translate(text, kind, target_lang, source_lang, &idempotency_key)
+                .await;
+        }
+
+        Err(last_error)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn translate_attempt(
+        &self,
+        provider: &Provider<'_>,
+        text: &str,
+        kind: TextKind,
+        target_lang: &str,
+        source_lang: Option<&str>,
+        idempotency_key: &str,
+        insist: bool,
+    ) -> Result<Translation> {
+        let (prompt, messages) = self.chat_messages(text, kind, target_lang, source_lang, insist);
+        let max_tokens = self.max_tokens_for(text, target_lang, source_lang);
+        let translation = self
+            .send_chat_request(provider, messages, max_tokens, idempotency_key)
+            .await?;
+        self.record_history(&prompt, &translation);
+        Ok(translation)
+    }
+
+    /// The messages of a translation request, along with the prompt kept in the history
+    fn chat_messages(
+        &self,
+        text: &str,
+        kind: TextKind,
+        target_lang: &str,
+        source_lang: Option<&str>,
+        insist: bool,
+    ) -> (String, Vec<ChatMessage>) {
+        let word = kind == TextKind::Word;
+        let prompt = if word {
+            self.build_word_translation_prompt(text, target_lang, source_lang)
+        } else {
+            self.build_line_translation_prompt(text, target_lang, source_lang)
+        };
+
+        let mut messages = vec![ChatMessage {
+            role: "system".to_string(),
+            content: self.system_prompt(word),
+        }];
+        if self.protect_placeholders && placeholders::has_sentinels(text) {
+            messages[0].content.push_str(placeholders::PROMPT_RULE);
+        }
+        self.push_history_messages(&mut messages);
+        messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: if insist {
+                format!("{}\n\n{}", prompt, INSIST_PROMPT)
+            } else {
+                prompt.clone()
+            },
+        });
+        (prompt, messages)
+    }
+
+    /// Wait before retry `overloads` + 1 after an overload: the base delay, doubled each time
+    fn overload_delay(&self, overloads: u32) -> Duration {
+        let base = Duration::from_secs(self.config.overload_base_delay());
+        base.saturating_mul(2u32.saturating_pow(overloads))
+            .min(MAX_OVERLOAD_DELAY.max(base))
+    }
+
+    /// Append earlier turns as prior conversation messages
+    fn push_history_messages(&self, messages: &mut Vec<ChatMessage>) {
+        let Some(history) = &self.history else {
+            return;
+        };
+
+        for turn in history.lock().unwrap().turns() {
+            messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: turn.prompt.clone(),
+            });
+            messages.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: turn.translation.clone(),
+            });
         }
+    }
+
+    fn record_history(&self, prompt: &str, translation: &Translation) {
+        if let Some(history) = &self.history {
+            history.lock().unwrap().push(prompt, &translation.text);
+        }
+    }
+
+    /// Send a chat completion request and return the cleaned translation
+    async fn send_chat_request(
+        &self,
+        provider: &Provider<'_>,
+        messages: Vec<ChatMessage>,
+        max_tokens: Option<i32>,
+        idempotency_key: &str,
+    ) -> Result<Translation> {
+        let (req_builder, estimated_input) =
+            self.chat_request(provider, messages, max_tokens, idempotency_key, false);
+        let (status, body) =
+            debug_bundle::execute(&self.client, req_builder, self.debug_bundle.as_deref()).await?;
+
+        if !status.is_success() {
+            return Err(ApiError { status, body }.into());
+        }
+
+        let chat_response: ChatResponse =
+            serde_json::from_str(&body).context("Failed to parse API response")?;
+        self.chat_translation(chat_response, estimated_input)
+    }
 
-        let response = req_builder
+    /// Send a streaming chat completion request, passing the answer so far to `on_partial`
+    async fn stream_chat_request<F>(
+        &self,
+        provider: &Provider<'_>,
+        messages: Vec<ChatMessage>,
+        max_tokens: Option<i32>,
+        idempotency_key: &str,
+        mut on_partial: F,
+    ) -> Result<Translation>
+    where
+        F: FnMut(&str),
+    {
+        let (req_builder, estimated_input) =
+            self.chat_request(provider, messages, max_tokens, idempotency_key, true);
+        let mut response = req_builder
             .send()
             .await
-            .context("Failed to send word translation request")?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow!(
-                "API request failed with status {}: {}",
-                status,
-                error_text
-            ));
+            .context("Failed to send translation request")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ApiError { status, body }.into());
         }
 
-        let chat_response: ChatResponse = response
-            .json()
+        // Endpoints that don't support streaming answer with a whole response instead
+        let is_event_stream = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !is_event_stream {
+            let body = response
+                .text()
+                .await
+                .context("Failed to read API response")?;
+            let chat_response: ChatResponse =
+                serde_json::from_str(&body).context("Failed to parse API response")?;
+            return self.chat_translation(chat_response, estimated_input);
+        }
+
+        let mut events = EventBuffer::default();
+        let mut content = String::new();
+        let mut reasoning = String::new();
+        let mut usage = None;
+        while let Some(chunk) = response
+            .chunk()
             .await
-            .context("Failed to parse API response")?;
+            .context("Failed to read API response")?
+        {
+            for data in events.push(&chunk) {
+                if data == "[DONE]" {
+                    continue;
+                }
+                let chunk: StreamChunk =
+                    serde_json::from_str(&data).context("Failed to parse API response")?;
+                if let Some(delta) = chunk.choices.into_iter().next().and_then(|c| c.delta) {
+                    reasoning.push_str(delta.reasoning_content.as_deref().unwrap_or(""));
+                    if let Some(text) = delta.content
+                        && !text.is_empty()
+                    {
+                        content.push_str(&text);
+                        on_partial(content.trim());
+                    }
+                }
+                usage = chunk.usage.or(usage);
+            }
+        }
+
+        let chat_response = ChatResponse {
+            choices: vec![ChatChoice {
+                message: Some(ResponseMessage {
+                    content: Some(content),
+                    reasoning_content: Some(reasoning),
+                }),
+                text: None,
+                logprobs: None,
+            }],
+            usage,
+        };
+        self.chat_translation(chat_response, estimated_input)
+    }
+
+    /// Build a chat completion request, along with an estimate of its input tokens
+    fn chat_request(
+        &self,
+        provider: &Provider<'_>,
+        messages: Vec<ChatMessage>,
+        max_tokens: Option<i32>,
+        idempotency_key: &str,
+        stream: bool,
+    ) -> (RequestBuilder, usize) {
+        // Used for the spending cap when the endpoint doesn't report usage
+        let estimated_input: usize = messages
+            .iter()
+            .map(|message| tokens::estimate_tokens(&message.content))
+            .sum();
 
-        if chat_response.choices.is_empty() {
-            return Err(anyhow!("No translation choices returned from API"));
+        let prompt_caching = self.config.prompt_caching() && provider.supports_cache_markers();
+        let request = ChatRequest {
+            model: provider.model.to_string(),
+            messages: messages
+                .into_iter()
+                .map(|message| RequestMessage::from_chat(message, prompt_caching))
+                .collect(),
+            temperature: self
+                .preset
+                .as_ref()
+                .and_then(|preset| preset.temperature)
+                .unwrap_or(self.config.temperature()),
+            max_tokens,
+            logprobs: self.with_confidence.then_some(true),
+            top_logprobs: self.with_confidence.then_some(1),
+            stream: stream.then_some(true),
+            // Streams only report usage when asked to
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+        };
+
+        let url = format!("{}/chat/completions", provider.endpoint);
+        let mut req_builder = self
+            .client
+            .post(&url)
+            .header("X-Idempotency-Key", idempotency_key)
+            .json(&request);
+
+        // Add authorization header if API key is available
+        if let Some(api_key) = provider.api_key
+            && !api_key.is_empty()
+        {
+            req_builder = req_builder.header("Authorization", format!("Bearer {}", api_key));
         }
+        (req_builder, estimated_input)
+    }
+
+    /// The cleaned translation of a chat completion response
+    fn chat_translation(
+        &self,
+        chat_response: ChatResponse,
+        estimated_input: usize,
+    ) -> Result<Translation> {
+        // Tokens are billed even when the answer turns out to be empty
+        self.add_spending(&chat_response, estimated_input);
 
-        let translated_text = chat_response.choices[0].message.content.trim();
+        let choice = chat_response.choices.first().ok_or(EmptyResponse)?;
+        let mut translated_text = choice.content().ok_or(EmptyResponse)?;
+
+        // Extract only the answer when the prompt makes the model add extra framing
+        if let Some(extractor) = &self.answer_extractor
+            && let Some(answer) = extractor.captures(translated_text).and_then(|c| c.get(1))
+        {
+            translated_text = answer.as_str();
+        }
 
         // Remove quotes if the response is wrapped in them
-        let cleaned_text = if (translated_text.starts_with('"') && translated_text.ends_with('"'))
-            || (translated_text.starts_with('\'') && translated_text.ends_with('\''))
+        let text = strip_quotes(translated_text.trim());
+        if text.is_empty() {
+            // Reasoning models sometimes think without ever producing the answer
+            if self.verbose && choice.reasoning().is_some() {
+                output::notice("Model returned reasoning but no answer, retrying");
+            }
+            return Err(EmptyResponse.into());
+        }
+
+        let usage = chat_response.usage.as_ref();
+        Ok(Translation {
+            text: text.to_string(),
+            confidence: choice.logprobs.as_ref().and_then(average_probability),
+            reasoning: choice.reasoning().map(str::to_string),
+            reasoning_tokens: usage
+                .and_then(|usage| usage.completion_tokens_details.as_ref())
+                .and_then(|details| details.reasoning_tokens),
+            input_tokens: usage.and_then(|usage| usage.prompt_tokens),
+            output_tokens: usage.and_then(|usage| usage.completion_tokens),
+        })
+    }
+
+    fn check_cost_limit(&self) -> Result<()> {
+        if let Some(limit) = &self.cost_limit
+            && *limit.spent.lock().unwrap() >= limit.max_cost
         {
-            &translated_text[1..translated_text.len() - 1]
+            return Err(CostLimitReached {
+                max_cost: limit.max_cost,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn add_spending(&self, response: &ChatResponse, estimated_input: usize) {
+        let Some(limit) = &self.cost_limit else {
+            return;
+        };
+
+        let usage = response.usage.as_ref();
+        let input = usage
+            .and_then(|usage| usage.prompt_tokens)
+            .unwrap_or(estimated_input);
+        let output = usage
+            .and_then(|usage| usage.completion_tokens)
+            .unwrap_or_else(|| {
+                response
+                    .choices
+                    .iter()
+                    .filter_map(ChatChoice::content)
+                    .map(tokens::estimate_tokens)
+                    .sum()
+            });
+        *limit.spent.lock().unwrap() += limit.pricing.cost(input, output);
+    }
+
+    fn max_tokens_for(
+        &self,
+        text: &str,
+        target_lang: &str,
+        source_lang: Option<&str>,
+    ) -> Option<i32> {
+        // The estimate is sized for a translation, not for a definition on top
+        if self.auto_max_tokens && !self.bidirectional {
+            Some(tokens::estimate_max_tokens(text, source_lang, target_lang))
+        } else {
+            self.config.max_tokens()
+        }
+    }
+
+    fn primary_provider(&self) -> Provider<'_> {
+        Provider {
+            endpoint: self.config.endpoint(),
+            model: self.config.model(),
+            api_key: self.config.api_key(),
+        }
+    }
+
+    fn fallback_provider(&self) -> Option<Provider<'_>> {
+        self.config.fallback().map(|fallback| Provider {
+            endpoint: &fallback.endpoint,
+            model: &fallback.model,
+            api_key: fallback.api_key.as_deref(),
+        })
+    }
+
+    fn audit(&self, model: &str, text: &str, target_lang: &str, source_lang: Option<&str>) {
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record(model, text, target_lang, source_lang);
+        }
+    }
+
+    fn log_fallback(&self, endpoint: &str, model: &str) {
+        if self.verbose {
+            output::notice(&format!(
+                "Primary endpoint failed, falling back to {} ({})",
+                endpoint, model
+            ));
+        }
+    }
+
+    /// The built-in or preset system prompt for words or text, with the preset's rules
+    fn system_prompt(&self, word: bool) -> String {
+        let card = word && self.bidirectional;
+        let default = if card {
+            card::SYSTEM_PROMPT
+        } else if word {
+            WORD_SYSTEM_PROMPT
         } else {
-            translated_text
+            LINE_SYSTEM_PROMPT
+        };
+        let Some(preset) = &self.preset else {
+            return default.to_string();
         };
 
-        Ok(cleaned_text.to_string())
+        // Card replies are parsed, so a preset may only add rules to their prompt
+        let mut prompt = match preset.system_prompt(word) {
+            Some(custom) if !card => custom.to_string(),
+            _ => default.to_string(),
+        };
+        for rule in &preset.rules {
+            prompt.push_str("\n- ");
+            prompt.push_str(rule);
+        }
+        prompt
+    }
+
+    /// The request filled in from the preset's template, if it has one
+    fn preset_prompt(
+        &self,
+        word: bool,
+        text: &str,
+        target_lang: &str,
+        source_lang: Option<&str>,
+    ) -> Option<String> {
+        let source = source_lang.map(|source| self.lang_code_to_name(source));
+        self.preset.as_ref()?.user_prompt(
+            word,
+            text,
+            &self.lang_code_to_name(target_lang),
+            source.as_deref(),
+        )
     }
 
     fn build_line_translation_prompt(
@@ -289,6 +1444,9 @@ impl Translator {
         target_lang: &str,
         source_lang: Option<&str>,
     ) -> String {
+        if let Some(prompt) = self.preset_prompt(false, text, target_lang, source_lang) {
+            return prompt;
+        }
         let target_lang_name = self.lang_code_to_name(target_lang);
 
         match source_lang {
@@ -299,12 +1457,16 @@ impl Translator {
                     source_lang_name, target_lang_name, text
                 )
             }
-            None => {
-                format!(
+            None => match self.source_hint_name() {
+                Some(hint) => format!(
+                    "Translate the following text to {}. The source is likely {}, but if the text is clearly in another language, translate from that language instead:\n\n{}",
+                    target_lang_name, hint, text
+                ),
+                None => format!(
                     "Translate the following text to {}:\n\n{}",
                     target_lang_name, text
-                )
-            }
+                ),
+            },
         }
     }
 
@@ -314,6 +1476,9 @@ impl Translator {
         target_lang: &str,
         source_lang: Option<&str>,
     ) -> String {
+        if let Some(prompt) = self.preset_prompt(true, word, target_lang, source_lang) {
+            return prompt;
+        }
         let target_lang_name = self.lang_code_to_name(target_lang);
 
         match source_lang {
@@ -324,12 +1489,23 @@ impl Translator {
                     source_lang_name, target_lang_name, word
                 )
             }
-            None => {
-                format!("Translate this word to {}: {}", target_lang_name, word)
-            }
+            None => match self.source_hint_name() {
+                Some(hint) => format!(
+                    "Translate this word to {} (it is likely {}, unless it is clearly another language): {}",
+                    target_lang_name, hint, word
+                ),
+                None => format!("Translate this word to {}: {}", target_lang_name, word),
+            },
         }
     }
 
+    /// Name of the hinted source language; a hard source language takes precedence
+    fn source_hint_name(&self) -> Option<String> {
+        self.source_hint
+            .as_deref()
+            .map(|hint| self.lang_code_to_name(hint))
+    }
+
     fn lang_code_to_name(&self, code: &str) -> String {
         match code {
             "zh" | "zh-cn" => "Chinese".to_string(),
@@ -344,6 +1520,9 @@ impl Translator {
             "pt" => "Portuguese".to_string(),
             "ru" => "Russian".to_string(),
             "ar" => "Arabic".to_string(),
+            "he" => "Hebrew".to_string(),
+            "fa" => "Persian".to_string(),
+            "ur" => "Urdu".to_string(),
             "hi" => "Hindi".to_string(),
             "th" => "Thai".to_string(),
             "vi" => "Vietnamese".to_string(),
@@ -351,3 +1530,1359 @@ impl Translator {
         }
     }
 }
+
+/// Average per-token probability, or None when no token logprobs were returned
+fn average_probability(logprobs: &ChoiceLogprobs) -> Option<f64> {
+    let tokens = logprobs.content.as_ref()?;
+    if tokens.is_empty() {
+        return None;
+    }
+
+    let total: f64 = tokens.iter().map(|token| token.logprob.exp()).sum();
+    Some(total / tokens.len() as f64)
+}
+
+/// Remove a single pair of quotes wrapping the whole text, if any
+fn strip_quotes(text: &str) -> &str {
+    const QUOTE_PAIRS: [(char, char); 7] = [
+        ('"', '"'),
+        ('\'', '\''),
+        ('“', '”'),
+        ('‘', '’'),
+        ('「', '」'),
+        ('『', '』'),
+        ('«', '»'),
+    ];
+
+    QUOTE_PAIRS
+        .iter()
+        .find_map(|&(open, close)| text.strip_prefix(open)?.strip_suffix(close))
+        .unwrap_or(text)
+}
+
+/// Whether a line is a bare number, URL or code identifier that should not be translated
+pub fn is_untranslatable(text: &str) -> bool {
+    let text = text.trim();
+    !text.is_empty() && (is_number(text) || is_url(text) || is_identifier(text))
+}
+
+fn is_number(text: &str) -> bool {
+    text.chars().any(|c| c.is_ascii_digit())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || " +-.,:/%_".contains(c))
+}
+
+fn is_url(text: &str) -> bool {
+    const PREFIXES: [&str; 5] = ["http://", "https://", "ftp://", "www.", "mailto:"];
+
+    !text.chars().any(char::is_whitespace)
+        && PREFIXES
+            .iter()
+            .any(|prefix| text.to_ascii_lowercase().starts_with(prefix))
+}
+
+fn is_identifier(text: &str) -> bool {
+    if !text
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_:.()".contains(c))
+    {
+        return false;
+    }
+
+    // Plain words also consist of these characters, so require a code-like shape.
+    // Names such as iPhone or McDonald have one hump, so camelCase needs two or a digit.
+    let bytes = text.as_bytes();
+    let humps = bytes
+        .windows(2)
+        .filter(|pair| pair[0].is_ascii_lowercase() && pair[1].is_ascii_uppercase())
+        .count();
+    let camel_case = humps >= 2 || (humps == 1 && bytes.iter().any(u8::is_ascii_digit));
+
+    text.contains('_') || text.contains("::") || text.ends_with("()") || camel_case
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::RecordingSleeper;
+    use crate::config::FallbackConfig;
+    use serde_json::json;
+    use std::pin::pin;
+    use wiremock::matchers::{
+        body_partial_json, body_string_contains, header, header_regex, method, path,
+    };
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_config(server: &MockServer) -> Config {
+        let mut config = Config::default();
+        config.set_endpoint(&server.uri());
+        config
+    }
+
+    /// A translator that records its retry delays instead of sleeping
+    fn recording_translator(config: &Config) -> (Translator, RecordingSleeper) {
+        let sleeper = RecordingSleeper::default();
+        let translator = Translator::with_parts(config, Client::new(), Box::new(sleeper.clone()));
+        (translator, sleeper)
+    }
+
+    async fn translate(translator: &Translator, text: &str) -> Result<Translation> {
+        let mut output = Translation::default();
+        translator
+            .translate_line(text, "de", None, |_, translation| {
+                output = translation.clone()
+            })
+            .await?;
+        Ok(output)
+    }
+
+    fn answer(text: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .set_body_json(json!({ "choices": [{ "message": { "content": text } }] }))
+    }
+
+    fn event_stream(deltas: &[&str]) -> ResponseTemplate {
+        let mut body = String::new();
+        for delta in deltas {
+            let event = json!({ "choices": [{ "delta": { "content": delta } }] });
+            body.push_str(&format!("data: {}\n\n", event));
+        }
+        body.push_str("data: [DONE]\n\n");
+        ResponseTemplate::new(200).set_body_raw(body, "text/event-stream")
+    }
+
+    #[tokio::test]
+    async fn live_translation_streams_partial_answers() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(json!({ "stream": true })))
+            .respond_with(event_stream(&["Hallo", " Welt"]))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        let mut partials = Vec::new();
+        let translation = translator
+            .translate_live("Hello world", TextKind::Line, "de", None, |partial| {
+                partials.push(partial.to_string())
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(partials, ["Hallo", "Hallo Welt"]);
+        assert_eq!(translation.text, "Hallo Welt");
+    }
+
+    #[tokio::test]
+    async fn live_translation_reads_usage_from_the_final_chunk() {
+        let server = MockServer::start().await;
+        let body = format!(
+            "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+            json!({ "choices": [{ "delta": { "content": "Hallo" } }] }),
+            json!({ "choices": [], "usage": { "prompt_tokens": 42, "completion_tokens": 3 } }),
+        );
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "stream": true,
+                "stream_options": { "include_usage": true }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        let translation = translator
+            .translate_live("Hello", TextKind::Line, "de", None, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(translation.text, "Hallo");
+        assert_eq!(
+            (translation.input_tokens, translation.output_tokens),
+            (Some(42), Some(3))
+        );
+    }
+
+    #[tokio::test]
+    async fn live_translation_accepts_a_whole_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo Welt"))
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        let translation = translator
+            .translate_live("Hello world", TextKind::Line, "de", None, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(translation.text, "Hallo Welt");
+    }
+
+    #[tokio::test]
+    async fn live_translation_retries_without_streaming() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "stream": true })))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo Welt"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        let translation = translator
+            .translate_live("Hello world", TextKind::Line, "de", None, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(translation.text, "Hallo Welt");
+    }
+
+    #[tokio::test]
+    async fn overloads_back_off_exponentially() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(529).set_body_string("Overloaded"))
+            .expect(4)
+            .mount(&server)
+            .await;
+
+        let mut config = test_config(&server);
+        config.overload_max_retries = 3;
+        config.overload_base_delay = 5;
+        let (translator, sleeper) = recording_translator(&config);
+
+        let error = translate(&translator, "Hello world").await.unwrap_err();
+        assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, 529);
+        assert_eq!(
+            sleeper.slept(),
+            [5, 10, 20].map(Duration::from_secs).to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn overload_delay_is_capped() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(529))
+            .mount(&server)
+            .await;
+
+        let mut config = test_config(&server);
+        config.overload_base_delay = 20;
+        let (translator, sleeper) = recording_translator(&config);
+
+        translate(&translator, "Hello world").await.unwrap_err();
+        assert_eq!(
+            sleeper.slept(),
+            [20, 40, 60, 60, 60, 60].map(Duration::from_secs).to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn recovers_after_an_overload() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(529))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo Welt"))
+            .mount(&server)
+            .await;
+
+        let (translator, sleeper) = recording_translator(&test_config(&server));
+        let translation = translate(&translator, "Hello world").await.unwrap();
+        assert_eq!(translation.text, "Hallo Welt");
+        assert_eq!(sleeper.slept(), [Duration::from_secs(5)]);
+    }
+
+    #[test]
+    fn numbers_urls_and_identifiers_are_untranslatable() {
+        for text in [
+            "3.14",
+            "12:30",
+            "50%",
+            "https://x.com",
+            "www.example.org/path",
+            "snake_case",
+            "std::mem",
+            "parse()",
+            "getUserName",
+            "getValue2",
+        ] {
+            assert!(is_untranslatable(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn words_and_names_are_translatable() {
+        for text in [
+            "",
+            "hello",
+            "iPhone",
+            "JavaScript",
+            "McDonald",
+            "Version 2",
+            "see https://x.com",
+        ] {
+            assert!(!is_untranslatable(text), "{}", text);
+        }
+    }
+
+    #[tokio::test]
+    async fn untranslatable_words_are_not_sent() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("unused"))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        for word in ["https://x.com", "3.14"] {
+            let mut output = Translation::default();
+            translator
+                .translate_word(word, "de", None, |_, translation| {
+                    output = translation.clone()
+                })
+                .await
+                .unwrap();
+            assert_eq!(output.text, word);
+        }
+    }
+
+    #[tokio::test]
+    async fn translate_all_sends_untranslatable_words() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("drei Komma eins vier"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_translate_all(true);
+        let mut output = Translation::default();
+        translator
+            .translate_word("3.14", "de", None, |_, translation| {
+                output = translation.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(output.text, "drei Komma eins vier");
+    }
+
+    fn provider(endpoint: &str) -> Provider<'_> {
+        Provider {
+            endpoint,
+            model: "model",
+            api_key: None,
+        }
+    }
+
+    #[test]
+    fn cache_markers_only_for_supporting_providers() {
+        assert!(provider("https://api.anthropic.com/v1").supports_cache_markers());
+        assert!(provider("https://openrouter.ai/api/v1").supports_cache_markers());
+        assert!(!provider("https://api.deepseek.com/v1").supports_cache_markers());
+        assert!(!provider("https://anthropic.com.example.org/v1").supports_cache_markers());
+        assert!(!provider("not a url").supports_cache_markers());
+    }
+
+    #[test]
+    fn system_prompt_is_marked_cacheable_when_enabled() {
+        let system = || ChatMessage {
+            role: "system".to_string(),
+            content: "Rules".to_string(),
+        };
+
+        let marked = serde_json::to_value(RequestMessage::from_chat(system(), true)).unwrap();
+        assert_eq!(
+            marked,
+            json!({
+                "role": "system",
+                "content": [{ "type": "text", "text": "Rules", "cache_control": { "type": "ephemeral" } }],
+            })
+        );
+        let plain = serde_json::to_value(RequestMessage::from_chat(system(), false)).unwrap();
+        assert_eq!(plain, json!({ "role": "system", "content": "Rules" }));
+    }
+
+    #[test]
+    fn user_messages_are_never_marked() {
+        let user = ChatMessage {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+        };
+        let message = serde_json::to_value(RequestMessage::from_chat(user, true)).unwrap();
+        assert_eq!(message, json!({ "role": "user", "content": "Hello" }));
+    }
+
+    #[tokio::test]
+    async fn cache_markers_are_left_out_for_other_providers() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo Welt"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = test_config(&server);
+        config.set_prompt_caching(true);
+        let translator = Translator::new(&config);
+        translate(&translator, "Hello world").await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = requests[0].body_json().unwrap();
+        assert!(body["messages"][0]["content"].is_string(), "{}", body);
+    }
+
+    fn spec(server: &MockServer, model: &str) -> ProviderSpec {
+        ProviderSpec {
+            endpoint: server.uri(),
+            model: model.to_string(),
+            api_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn consensus_collects_every_candidate_in_order() {
+        let first = MockServer::start().await;
+        let second = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo Welt"))
+            .expect(1)
+            .mount(&first)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Servus Welt"))
+            .expect(1)
+            .mount(&second)
+            .await;
+        let judge = MockServer::start().await;
+
+        let translator = Translator::new(&test_config(&judge));
+        let providers = [spec(&first, "a"), spec(&second, "b")];
+        let results = translator
+            .translate_consensus("Hello world", &providers, "de", None)
+            .await;
+
+        let texts: Vec<String> = results
+            .into_iter()
+            .map(|result| result.unwrap().text)
+            .collect();
+        assert_eq!(texts, ["Hallo Welt", "Servus Welt"]);
+    }
+
+    #[tokio::test]
+    async fn consensus_candidates_fail_independently() {
+        let working = MockServer::start().await;
+        let failing = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo Welt"))
+            .mount(&working)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&failing)
+            .await;
+
+        let translator = Translator::new(&test_config(&working));
+        let providers = [spec(&failing, "a"), spec(&working, "b")];
+        let results = translator
+            .translate_consensus("Hello world", &providers, "de", None)
+            .await;
+        assert!(results[0].is_err());
+        assert_eq!(results[1].as_ref().unwrap().text, "Hallo Welt");
+    }
+
+    #[tokio::test]
+    async fn judge_picks_the_numbered_candidate() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("2."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        let choice = translator
+            .judge("Hello world", &["Hallo Welt", "Servus Welt"], "de", None)
+            .await
+            .unwrap();
+        assert_eq!(choice, 1);
+
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = requests[0].body_json().unwrap();
+        let prompt = body["messages"][1]["content"].as_str().unwrap();
+        assert!(
+            prompt.contains("1. Hallo Welt\n2. Servus Welt"),
+            "{}",
+            prompt
+        );
+    }
+
+    #[tokio::test]
+    async fn judge_reply_must_name_a_candidate() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("3"))
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        let error = translator
+            .judge("Hello world", &["Hallo Welt", "Servus Welt"], "de", None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("does not name a candidate"));
+    }
+
+    #[tokio::test]
+    async fn stops_once_the_cost_limit_is_reached() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "content": "Hallo" } }],
+                "usage": { "prompt_tokens": 600, "completion_tokens": 600 },
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        let pricing = Pricing {
+            input: 1.0,
+            output: 1.0,
+        };
+        // Each request costs 0.0012, so the second one crosses the cap
+        translator.set_max_cost(pricing, 0.002);
+        translate(&translator, "Hello one").await.unwrap();
+        translate(&translator, "Hello two").await.unwrap();
+
+        let error = translate(&translator, "Hello three").await.unwrap_err();
+        assert!(error.is::<CostLimitReached>(), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn preset_overrides_the_prompt_and_temperature() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo Welt"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_preset(Some(Preset {
+            name: "formal".to_string(),
+            system_prompt: Some("You translate letters.".to_string()),
+            prompt: Some("Put this into {target}: {text}".to_string()),
+            rules: vec!["Use the formal register".to_string()],
+            temperature: Some(0.2),
+            ..Preset::default()
+        }));
+        translate(&translator, "Hello world").await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = requests[0].body_json().unwrap();
+        assert_eq!(
+            body["messages"][0]["content"],
+            "You translate letters.\n- Use the formal register"
+        );
+        assert_eq!(
+            body["messages"][1]["content"],
+            "Put this into German: Hello world"
+        );
+        assert_eq!(body["temperature"].as_f64().unwrap() as f32, 0.2);
+    }
+
+    #[tokio::test]
+    async fn preset_without_prompts_keeps_the_built_in_ones() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_preset(Some(Preset {
+            rules: vec!["Keep product names".to_string()],
+            ..Preset::default()
+        }));
+        let mut output = Translation::default();
+        translator
+            .translate_word("hello", "de", None, |_, translation| {
+                output = translation.clone()
+            })
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = requests[0].body_json().unwrap();
+        assert_eq!(
+            body["messages"][0]["content"],
+            format!("{}\n- Keep product names", WORD_SYSTEM_PROMPT)
+        );
+        assert_eq!(body["temperature"].as_f64().unwrap() as f32, 1.3);
+    }
+
+    #[tokio::test]
+    async fn list_markers_are_kept_on_every_line() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("one\\ntwo\\nthree"))
+            .respond_with(answer("eins\nzwei\ndrei"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_preserve_lists(true);
+        let translation = translate(&translator, "- one\n  - two\n1. three")
+            .await
+            .unwrap();
+        assert_eq!(translation.text, "- eins\n  - zwei\n1. drei");
+    }
+
+    #[tokio::test]
+    async fn list_lines_are_translated_alone_if_merged() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("one\\ntwo"))
+            .respond_with(answer("eins zwei"))
+            .with_priority(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        for (source, translated) in [("one", "eins"), ("two", "zwei")] {
+            Mock::given(method("POST"))
+                .and(body_string_contains(source))
+                .respond_with(answer(translated))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_preserve_lists(true);
+        let translation = translate(&translator, "1. one\n2. two").await.unwrap();
+        assert_eq!(translation.text, "1. eins\n2. zwei");
+    }
+
+    #[tokio::test]
+    async fn injected_client_sends_the_configured_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("Authorization", "Bearer secret"))
+            .and(header("X-Test", "injected"))
+            .and(body_partial_json(json!({ "model": "test-model" })))
+            .and(body_string_contains("Good morning"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "content": "\"Guten Morgen\"" } }],
+                "usage": { "prompt_tokens": 12, "completion_tokens": 4 }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = test_config(&server);
+        config.set_api_key("secret");
+        config.set_model("test-model");
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("X-Test", "injected".parse().unwrap());
+        let client = Client::builder().default_headers(headers).build().unwrap();
+
+        let translator = Translator::with_client(&config, client);
+        let translation = translate(&translator, "Good morning").await.unwrap();
+        assert_eq!(translation.text, "Guten Morgen");
+        assert_eq!(translation.input_tokens, Some(12));
+        assert_eq!(translation.output_tokens, Some(4));
+    }
+
+    #[tokio::test]
+    async fn injected_client_looks_up_words() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("bank"))
+            .respond_with(answer("Bank; Ufer"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let translator = Translator::with_client(&test_config(&server), Client::new());
+        let mut output = Translation::default();
+        translator
+            .translate_word("bank", "de", Some("en"), |_, translation| {
+                output = translation.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(output.text, "Bank; Ufer");
+    }
+
+    #[tokio::test]
+    async fn failed_requests_back_off_exponentially_with_one_key() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let (translator, sleeper) = recording_translator(&test_config(&server));
+        assert!(translate(&translator, "Hello").await.is_err());
+        assert_eq!(
+            sleeper.slept(),
+            [Duration::from_secs(1), Duration::from_secs(2)]
+        );
+
+        let keys = idempotency_keys(&server.received_requests().await.unwrap());
+        assert!(keys.iter().all(|key| *key == keys[0]));
+    }
+
+    #[tokio::test]
+    async fn recovers_after_a_failed_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .mount(&server)
+            .await;
+
+        let (translator, sleeper) = recording_translator(&test_config(&server));
+        assert_eq!(translate(&translator, "Hello").await.unwrap().text, "Hallo");
+        assert_eq!(sleeper.slept(), [Duration::from_secs(1)]);
+    }
+
+    #[tokio::test]
+    async fn empty_answer_is_retried_at_once_with_a_firmer_prompt() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("previous reply was empty"))
+            .respond_with(answer("Hallo"))
+            .with_priority(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(answer(""))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let (translator, sleeper) = recording_translator(&test_config(&server));
+        assert_eq!(translate(&translator, "Hello").await.unwrap().text, "Hallo");
+        assert!(sleeper.slept().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reasoning_without_an_answer_is_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("previous reply was empty"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": {
+                    "content": "Hallo",
+                    "reasoning_content": "A greeting"
+                } }],
+                "usage": {
+                    "prompt_tokens": 10,
+                    "completion_tokens": 20,
+                    "completion_tokens_details": { "reasoning_tokens": 15 }
+                }
+            })))
+            .with_priority(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "content": "", "reasoning_content": "Hmm" } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let (translator, sleeper) = recording_translator(&test_config(&server));
+        let translation = translate(&translator, "Hello").await.unwrap();
+        assert_eq!(translation.text, "Hallo");
+        assert_eq!(translation.reasoning.as_deref(), Some("A greeting"));
+        assert_eq!(translation.reasoning_tokens, Some(15));
+        assert!(sleeper.slept().is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_answers_give_up_after_the_insisting_retries() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer(""))
+            .expect(MAX_EMPTY_RETRIES as u64 + 1)
+            .mount(&server)
+            .await;
+
+        let (translator, _) = recording_translator(&test_config(&server));
+        let error = translate(&translator, "Hello").await.unwrap_err();
+        assert!(error.to_string().contains("empty translation"), "{}", error);
+    }
+
+    fn idempotency_keys(requests: &[wiremock::Request]) -> Vec<String> {
+        requests
+            .iter()
+            .map(|request| {
+                let key = request.headers.get("X-Idempotency-Key").unwrap();
+                key.to_str().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn every_translation_has_its_own_idempotency_key() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        translate(&translator, "Hello").await.unwrap();
+        translate(&translator, "Hello").await.unwrap();
+
+        let keys = idempotency_keys(&server.received_requests().await.unwrap());
+        assert_eq!(keys.len(), 2);
+        assert_ne!(keys[0], keys[1]);
+        assert!(Uuid::parse_str(&keys[0]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn insisting_after_an_empty_answer_uses_a_new_key() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("previous reply was empty"))
+            .respond_with(answer("Hallo"))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(answer(""))
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        translate(&translator, "Hello").await.unwrap();
+
+        let keys = idempotency_keys(&server.received_requests().await.unwrap());
+        assert_eq!(keys.len(), 2);
+        assert_ne!(keys[0], keys[1]);
+    }
+
+    #[tokio::test]
+    async fn fallback_provider_answers_after_the_primary_fails() {
+        let primary = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&primary)
+            .await;
+        let fallback = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("Authorization", "Bearer backup-key"))
+            .and(body_partial_json(json!({ "model": "backup-model" })))
+            .respond_with(answer("Hallo"))
+            .expect(1)
+            .mount(&fallback)
+            .await;
+
+        let mut config = test_config(&primary);
+        config.set_api_key("primary-key");
+        config.fallback = Some(FallbackConfig {
+            endpoint: fallback.uri(),
+            model: "backup-model".to_string(),
+            api_key: Some("backup-key".to_string()),
+        });
+        let (translator, sleeper) = recording_translator(&config);
+
+        assert_eq!(translate(&translator, "Hello").await.unwrap().text, "Hallo");
+        assert_eq!(sleeper.slept().len(), 2);
+
+        // Retries of the primary share a key, the fallback gets its own
+        let primary_keys = idempotency_keys(&primary.received_requests().await.unwrap());
+        let fallback_keys = idempotency_keys(&fallback.received_requests().await.unwrap());
+        assert!(primary_keys.iter().all(|key| *key == primary_keys[0]));
+        assert_ne!(fallback_keys[0], primary_keys[0]);
+    }
+
+    #[tokio::test]
+    async fn fallback_gets_a_single_chance() {
+        let primary = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&primary)
+            .await;
+        let fallback = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&fallback)
+            .await;
+
+        let mut config = test_config(&primary);
+        config.fallback = Some(FallbackConfig {
+            endpoint: fallback.uri(),
+            model: "backup-model".to_string(),
+            api_key: None,
+        });
+        let (translator, _) = recording_translator(&config);
+        assert!(translate(&translator, "Hello").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn answer_extractor_keeps_only_the_answer() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Thinking: a greeting.\nAnswer: \"Hallo\""))
+            .mount(&server)
+            .await;
+
+        let mut config = test_config(&server);
+        config.answer_extractor = Some(r"(?s)Answer:\s*(.*)".to_string());
+        let translator = Translator::new(&config);
+        assert_eq!(translate(&translator, "Hello").await.unwrap().text, "Hallo");
+    }
+
+    #[tokio::test]
+    async fn reply_without_a_match_is_kept_whole() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .mount(&server)
+            .await;
+
+        let mut config = test_config(&server);
+        config.answer_extractor = Some(r"Answer:\s*(.*)".to_string());
+        let translator = Translator::new(&config);
+        assert_eq!(translate(&translator, "Hello").await.unwrap().text, "Hallo");
+    }
+
+    #[test]
+    fn strips_matching_quote_pairs_of_any_width() {
+        assert_eq!(strip_quotes("\"Hallo\""), "Hallo");
+        assert_eq!(strip_quotes("“你好”"), "你好");
+        assert_eq!(strip_quotes("「こんにちは」"), "こんにちは");
+        assert_eq!(strip_quotes("«Bonjour»"), "Bonjour");
+        assert_eq!(strip_quotes("\"\""), "");
+    }
+
+    #[test]
+    fn keeps_lone_or_mismatched_quotes() {
+        assert_eq!(strip_quotes("\""), "\"");
+        assert_eq!(strip_quotes("“"), "“");
+        assert_eq!(strip_quotes("“Hallo\""), "“Hallo\"");
+        assert_eq!(strip_quotes("It's"), "It's");
+        assert_eq!(strip_quotes(""), "");
+    }
+
+    #[tokio::test]
+    async fn confidence_is_the_average_token_probability() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                json!({ "logprobs": true, "top_logprobs": 1 }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{
+                    "message": { "content": "Hallo" },
+                    "logprobs": { "content": [
+                        { "token": "Hal", "logprob": 0.0 },
+                        { "token": "lo", "logprob": (0.5f64).ln() }
+                    ] }
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_with_confidence(true);
+        let translation = translate(&translator, "Hello").await.unwrap();
+        let confidence = translation.confidence.unwrap();
+        assert!((confidence - 0.75).abs() < 1e-9, "{}", confidence);
+    }
+
+    #[tokio::test]
+    async fn confidence_is_unknown_without_logprobs() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_with_confidence(true);
+        assert_eq!(
+            translate(&translator, "Hello").await.unwrap().confidence,
+            None
+        );
+
+        let request = &server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = request.body_json().unwrap();
+        assert_eq!(body["logprobs"], true);
+
+        translator.set_with_confidence(false);
+        translate(&translator, "Hello").await.unwrap();
+        let request = &server.received_requests().await.unwrap()[1];
+        let body: serde_json::Value = request.body_json().unwrap();
+        assert!(body.get("logprobs").is_none());
+    }
+
+    #[tokio::test]
+    async fn batch_keeps_input_order_and_fails_items_alone() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("slow"))
+            .respond_with(answer("langsam").set_delay(Duration::from_millis(200)))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("broken"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("fast"))
+            .respond_with(answer("schnell"))
+            .mount(&server)
+            .await;
+
+        let (translator, _) = recording_translator(&test_config(&server));
+        let results = translator
+            .translate_batch(&["slow", "broken", "fast"], "de", None, 3)
+            .await;
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().text, "langsam");
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap().text, "schnell");
+    }
+
+    #[tokio::test]
+    async fn batch_sends_up_to_concurrency_requests_at_once() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("x").set_delay(Duration::from_millis(300)))
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        let started = std::time::Instant::now();
+        let results = translator
+            .translate_batch(&["a", "b", "c", "d"], "de", None, 4)
+            .await;
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(server.received_requests().await.unwrap().len(), 4);
+        // One round trip, not four in a row
+        assert!(started.elapsed() < Duration::from_millis(1000));
+    }
+
+    fn choice(value: serde_json::Value) -> ChatChoice {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn choice_content_comes_from_message_or_text() {
+        assert_eq!(
+            choice(json!({ "message": { "content": "chat" }, "text": "completion" })).content(),
+            Some("chat")
+        );
+        assert_eq!(
+            choice(json!({ "text": "completion" })).content(),
+            Some("completion")
+        );
+        assert_eq!(
+            choice(json!({ "message": { "role": "assistant" }, "text": "completion" })).content(),
+            Some("completion")
+        );
+        assert_eq!(choice(json!({})).content(), None);
+    }
+
+    #[tokio::test]
+    async fn completion_style_response_is_accepted() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "choices": [{ "text": "Hallo" }] })),
+            )
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        assert_eq!(translate(&translator, "Hello").await.unwrap().text, "Hallo");
+    }
+
+    #[tokio::test]
+    async fn choice_without_content_counts_as_empty() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "choices": [{ "finish_reason": "content_filter" }] })),
+            )
+            .expect(MAX_EMPTY_RETRIES as u64 + 1)
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        let error = translate(&translator, "Hello").await.unwrap_err();
+        assert!(error.to_string().contains("empty translation"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn auto_max_tokens_is_sized_from_the_input() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .mount(&server)
+            .await;
+
+        let mut config = test_config(&server);
+        config.set_max_tokens(Some(2000));
+        let mut translator = Translator::new(&config);
+        translator.set_auto_max_tokens(true);
+        let long = "word ".repeat(100);
+        translate(&translator, "Hi").await.unwrap();
+        translate(&translator, &long).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let max_tokens: Vec<serde_json::Value> = requests
+            .iter()
+            .map(|request| request.body_json::<serde_json::Value>().unwrap()["max_tokens"].clone())
+            .collect();
+        assert_eq!(
+            max_tokens,
+            [
+                json!(tokens::estimate_max_tokens("Hi", None, "de")),
+                json!(tokens::estimate_max_tokens(long.trim(), None, "de"))
+            ]
+        );
+        assert!(max_tokens[1].as_i64().unwrap() > max_tokens[0].as_i64().unwrap());
+    }
+
+    #[tokio::test]
+    async fn history_sends_earlier_turns_as_conversation() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_history(5);
+        translate(&translator, "Hello").await.unwrap();
+        translate(&translator, "Hello again").await.unwrap();
+        translator.clear_history();
+        translate(&translator, "Bye").await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let roles = |index: usize| -> Vec<String> {
+            let body: serde_json::Value = requests[index].body_json().unwrap();
+            body["messages"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|message| message["role"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(roles(0), ["system", "user"]);
+        assert_eq!(roles(1), ["system", "user", "assistant", "user"]);
+        assert_eq!(roles(2), ["system", "user"]);
+
+        let body: serde_json::Value = requests[1].body_json().unwrap();
+        assert!(
+            body["messages"][1]["content"]
+                .as_str()
+                .unwrap()
+                .ends_with("Hello")
+        );
+        assert_eq!(body["messages"][2]["content"], "Hallo");
+    }
+
+    #[tokio::test]
+    async fn empty_choices_are_retried_apart_from_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "choices": [] })))
+            .up_to_n_times(2)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .mount(&server)
+            .await;
+
+        // Two empty answers fit in the empty retries without using up the error retries
+        let (translator, sleeper) = recording_translator(&test_config(&server));
+        assert_eq!(translate(&translator, "Hello").await.unwrap().text, "Hallo");
+        assert!(sleeper.slept().is_empty());
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[test]
+    fn source_hint_is_a_soft_preference() {
+        let mut translator = Translator::new(&Config::default());
+        translator.set_source_hint(Some("ja".to_string()));
+
+        let prompt = translator.build_line_translation_prompt("日本", "en", None);
+        assert!(
+            prompt.contains("The source is likely Japanese"),
+            "{}",
+            prompt
+        );
+        let prompt = translator.build_word_translation_prompt("日本", "en", None);
+        assert!(prompt.contains("(it is likely Japanese"), "{}", prompt);
+
+        // A hard source language takes precedence
+        let prompt = translator.build_line_translation_prompt("日本", "en", Some("zh"));
+        assert!(prompt.starts_with("Translate the following text from Chinese to English"));
+        assert!(!prompt.contains("likely"));
+    }
+
+    #[test]
+    fn no_hint_leaves_the_prompt_as_it_was() {
+        let translator = Translator::new(&Config::default());
+        assert_eq!(
+            translator.build_line_translation_prompt("Hallo", "en", None),
+            "Translate the following text to English:\n\nHallo"
+        );
+        assert_eq!(
+            translator.build_word_translation_prompt("Hallo", "en", None),
+            "Translate this word to English: Hallo"
+        );
+    }
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn compressed_responses_are_decoded() {
+        let server = MockServer::start().await;
+        let body = json!({ "choices": [{ "message": { "content": "Hallo" } }] }).to_string();
+        Mock::given(method("POST"))
+            .and(header_regex("Accept-Encoding", "gzip"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Encoding", "gzip")
+                    .insert_header("Content-Type", "application/json")
+                    .set_body_bytes(gzip(body.as_bytes())),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let translator = Translator::new(&test_config(&server));
+        assert_eq!(translate(&translator, "Hello").await.unwrap().text, "Hallo");
+    }
+
+    #[tokio::test]
+    async fn compression_can_be_turned_off() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .mount(&server)
+            .await;
+
+        let mut config = test_config(&server);
+        config.compression = false;
+        translate(&Translator::new(&config), "Hello").await.unwrap();
+
+        let request = &server.received_requests().await.unwrap()[0];
+        assert!(request.headers.get("Accept-Encoding").is_none());
+    }
+
+    #[tokio::test]
+    async fn stream_yields_each_line_before_the_input_ends() {
+        let server = MockServer::start().await;
+        for (source, translated) in [("first", "erste"), ("second", "zweite")] {
+            Mock::given(method("POST"))
+                .and(body_string_contains(source))
+                .respond_with(answer(translated))
+                .mount(&server)
+                .await;
+        }
+
+        let translator = Translator::new(&test_config(&server));
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        let mut results = pin!(translator.translate_stream(receiver, "de", None, 2));
+
+        sender.unbounded_send("first".to_string()).unwrap();
+        let (line, result) = results.next().await.unwrap();
+        assert_eq!(
+            (line.as_str(), result.unwrap().text.as_str()),
+            ("first", "erste")
+        );
+
+        sender.unbounded_send("second".to_string()).unwrap();
+        drop(sender);
+        let (line, result) = results.next().await.unwrap();
+        assert_eq!(
+            (line.as_str(), result.unwrap().text.as_str()),
+            ("second", "zweite")
+        );
+        assert!(results.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn placeholders_are_sent_as_sentinels_and_restored() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("Hello ⟦1⟧"))
+            .and(body_string_contains("Keep placeholders like"))
+            .respond_with(answer("Hallo ⟦1⟧"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_protect_placeholders(true);
+        assert_eq!(
+            translate(&translator, "Hello {name}").await.unwrap().text,
+            "Hallo {name}"
+        );
+    }
+
+    #[tokio::test]
+    async fn translation_that_keeps_dropping_a_placeholder_fails() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(answer("Hallo"))
+            .expect(MAX_PLACEHOLDER_RETRIES as u64 + 1)
+            .mount(&server)
+            .await;
+
+        let mut translator = Translator::new(&test_config(&server));
+        translator.set_protect_placeholders(true);
+        let error = translate(&translator, "Hello {name}").await.unwrap_err();
+        assert!(error.is::<placeholders::PlaceholderLost>(), "{}", error);
+    }
+}
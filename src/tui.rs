@@ -0,0 +1,543 @@
+use anyhow::Result;
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use std::rc::Rc;
+use std::time::Instant;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use unicode_width::UnicodeWidthChar;
+
+use crate::ConfigReloader;
+use crate::backend::TextKind;
+use crate::has_blank;
+use crate::output;
+use crate::stats::SessionStats;
+use crate::text;
+use crate::theme::{TuiRole, tui_style};
+use crate::translator::Translator;
+
+/// Target languages cycled through with Tab
+const LANGUAGES: [&str; 8] = ["zh", "en", "ja", "ko", "fr", "de", "es", "ru"];
+
+/// One submitted input and its translation, once it arrives
+struct Entry {
+    original: String,
+    target: String,
+    /// The answer received so far while it streams in
+    partial: String,
+    translation: Option<Result<String, String>>,
+}
+
+/// What the event loop should do after a key press
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    /// Translate the entry at this index
+    Submit(usize),
+    /// Forget the session history
+    ClearHistory,
+    Quit,
+}
+
+/// State of the split-pane interactive session
+struct App {
+    entries: Vec<Entry>,
+    input: String,
+    languages: Vec<String>,
+    language: usize,
+    /// Number of entries scrolled up from the latest one. Both panes scroll by
+    /// entry, so they show the same entries however their lines wrap.
+    scroll: usize,
+    /// Short message shown in the input pane, e.g. after a config reload
+    notice: Option<String>,
+    /// Embed translations into right-to-left languages for the terminal
+    bidi: bool,
+}
+
+impl App {
+    fn new(target: &str, bidi: bool) -> Self {
+        let mut languages: Vec<String> = LANGUAGES.iter().map(|lang| lang.to_string()).collect();
+        let language = match languages.iter().position(|lang| lang == target) {
+            Some(index) => index,
+            None => {
+                languages.insert(0, target.to_string());
+                0
+            }
+        };
+
+        Self {
+            entries: Vec::new(),
+            input: String::new(),
+            languages,
+            language,
+            scroll: 0,
+            notice: None,
+            bidi,
+        }
+    }
+
+    fn target(&self) -> &str {
+        &self.languages[self.language]
+    }
+
+    /// Entries up to the one scrolled to
+    fn visible_entries(&self) -> &[Entry] {
+        &self.entries[..self.entries.len() - self.scroll]
+    }
+
+    /// Scroll up by `entries`, keeping at least the first entry in view
+    fn scroll_up(&mut self, entries: usize) {
+        self.scroll = (self.scroll + entries).min(self.entries.len().saturating_sub(1));
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('c') | KeyCode::Char('d')
+                if key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                return Action::Quit;
+            }
+            KeyCode::Enter => {
+                let text = self.input.trim().to_string();
+                self.input.clear();
+                if text.is_empty() {
+                    return Action::None;
+                }
+                if text == "/clear" {
+                    self.notice = Some("History cleared".to_string());
+                    return Action::ClearHistory;
+                }
+
+                self.entries.push(Entry {
+                    original: text,
+                    target: self.target().to_string(),
+                    partial: String::new(),
+                    translation: None,
+                });
+                self.scroll = 0;
+                return Action::Submit(self.entries.len() - 1);
+            }
+            KeyCode::Tab => self.language = (self.language + 1) % self.languages.len(),
+            KeyCode::BackTab => {
+                self.language = (self.language + self.languages.len() - 1) % self.languages.len()
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Up => self.scroll_up(1),
+            KeyCode::PageUp => self.scroll_up(10),
+            KeyCode::Down => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::Char(c) => self.input.push(c),
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn update(&mut self, index: usize, partial: String) {
+        // A late update must not replace the finished translation
+        if let Some(entry) = self.entries.get_mut(index)
+            && entry.translation.is_none()
+        {
+            entry.partial = partial;
+        }
+    }
+
+    fn finish(&mut self, index: usize, result: Result<String, String>) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            entry.translation = Some(result);
+        }
+    }
+}
+
+/// Run the split-pane interactive mode until the user quits
+pub async fn run(
+    translator: Translator,
+    to: &str,
+    from: Option<&str>,
+    reloader: Option<&mut ConfigReloader>,
+    stats: &mut SessionStats,
+    bidi: bool,
+) -> Result<()> {
+    // ratatui::init also installs a panic hook that restores the terminal
+    let mut terminal = ratatui::init();
+    // Retry and fallback notices would garble the screen if printed to stderr
+    let notices = output::capture_notices();
+    let result = event_loop(
+        &mut terminal,
+        translator,
+        App::new(to, bidi),
+        from,
+        reloader,
+        stats,
+        notices,
+    )
+    .await;
+    output::release_notices();
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    translator: Translator,
+    mut app: App,
+    from: Option<&str>,
+    mut reloader: Option<&mut ConfigReloader>,
+    stats: &mut SessionStats,
+    mut notices: UnboundedReceiver<String>,
+) -> Result<()> {
+    // Pending translations keep using the translator they were started with
+    let mut translator = Rc::new(translator);
+    let mut events = EventStream::new();
+    let mut pending = FuturesUnordered::new();
+    let (partial_sender, mut partials) = mpsc::unbounded_channel();
+
+    loop {
+        terminal.draw(|frame| draw(frame, &app))?;
+
+        tokio::select! {
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                    match app.handle_key(key) {
+                        Action::Quit => break,
+                        Action::ClearHistory => translator.clear_history(),
+                        Action::Submit(index) => {
+                            if let Some(result) = reloader.as_deref_mut().and_then(|reloader| reloader.reload(&translator)) {
+                                app.notice = Some(match result {
+                                    Ok(reloaded) => {
+                                        translator = Rc::new(reloaded);
+                                        "Config reloaded".to_string()
+                                    }
+                                    Err(e) => format!("Failed to reload config: {}", e),
+                                });
+                            }
+
+                            let text = app.entries[index].original.clone();
+                            let target = app.entries[index].target.clone();
+                            let translator = Rc::clone(&translator);
+                            let partial_sender = partial_sender.clone();
+                            pending.push(async move {
+                                let started = Instant::now();
+                                let kind = if has_blank(&text) { TextKind::Line } else { TextKind::Word };
+                                let result = translator
+                                    .translate_live(&text, kind, &target, from, |partial| {
+                                        let _ = partial_sender.send((index, partial.to_string()));
+                                    })
+                                    .await;
+                                (index, text, result, started.elapsed())
+                            });
+                        }
+                        Action::None => {}
+                    }
+                }
+                // Resizes and other events only need a redraw
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+                None => break,
+            },
+            Some((index, text, result, latency)) = pending.next(), if !pending.is_empty() => {
+                match &result {
+                    Ok(translation) => stats.record(&text, translation, latency),
+                    Err(_) => stats.record_failure(),
+                }
+                app.finish(index, result.map(|translation| translation.text).map_err(|e| e.to_string()));
+            }
+            Some((index, partial)) = partials.recv() => app.update(index, partial),
+            Some(notice) = notices.recv() => app.notice = Some(notice),
+        }
+    }
+
+    Ok(())
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [history_area, translation_area, input_area] = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Min(3),
+        Constraint::Length(3),
+    ])
+    .areas(frame.area());
+
+    let history: Vec<Line> = app
+        .visible_entries()
+        .iter()
+        .flat_map(|entry| wrap(&entry.original, inner_width(history_area)))
+        .map(Line::from)
+        .collect();
+    render_pane(frame, history_area, " Input ", history);
+
+    let translations = translation_lines(app, inner_width(translation_area));
+    let title = format!(" Translation ({}) ", app.target());
+    render_pane(frame, translation_area, &title, translations);
+
+    let mut input_block = Block::new().borders(Borders::ALL).title(" tzh> ");
+    if let Some(notice) = &app.notice {
+        input_block = input_block.title(
+            Line::styled(format!(" {} ", notice), tui_style(TuiRole::Status)).right_aligned(),
+        );
+    }
+    let input = Paragraph::new(app.input.as_str()).block(
+        input_block.title_bottom(" Enter: translate  Tab: language  ↑/↓: scroll  Esc: quit "),
+    );
+    frame.render_widget(input, input_area);
+
+    let cursor = app.input.chars().filter_map(|c| c.width()).sum::<usize>();
+    frame.set_cursor_position((
+        input_area.x + 1 + cursor.min(inner_width(input_area)) as u16,
+        input_area.y + 1,
+    ));
+}
+
+/// Lines of the translation pane, wrapped to `width`
+fn translation_lines(app: &App, width: usize) -> Vec<Line<'static>> {
+    app.visible_entries()
+        .iter()
+        .flat_map(|entry| {
+            let (text, style, translated) = match &entry.translation {
+                None if entry.partial.is_empty() => (
+                    "Translating...".to_string(),
+                    tui_style(TuiRole::Status),
+                    false,
+                ),
+                None => (entry.partial.clone(), tui_style(TuiRole::Translation), true),
+                Some(Ok(text)) => (text.clone(), tui_style(TuiRole::Translation), true),
+                Some(Err(e)) => (
+                    format!("Translation failed: {}", e),
+                    tui_style(TuiRole::Error),
+                    false,
+                ),
+            };
+            // Wrapped first, the controls take no columns
+            let embed = translated && app.bidi && text::is_rtl_language(&entry.target);
+            wrap(&text, width).into_iter().map(move |line| {
+                let line = if embed { text::embed_rtl(&line) } else { line };
+                Line::styled(line, style)
+            })
+        })
+        .collect()
+}
+
+/// Render lines into a bordered pane, keeping the last lines visible
+fn render_pane(frame: &mut Frame, area: Rect, title: &str, lines: Vec<Line>) {
+    let height = area.height.saturating_sub(2) as usize;
+    let top = lines.len().saturating_sub(height);
+
+    let pane =
+        Paragraph::new(lines[top..].to_vec()).block(Block::new().borders(Borders::ALL).title(
+            Span::styled(title, Style::new().add_modifier(Modifier::BOLD)),
+        ));
+    frame.render_widget(pane, area);
+}
+
+fn inner_width(area: Rect) -> usize {
+    area.width.saturating_sub(2).max(1) as usize
+}
+
+/// Split text into lines no wider than `width` terminal columns
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for source_line in text.lines() {
+        let mut line = String::new();
+        let mut line_width = 0;
+
+        for c in source_line.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if line_width + char_width > width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            line.push(c);
+            line_width += char_width;
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(app: &mut App, code: KeyCode) -> Action {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn submit_adds_an_entry_and_clears_the_input() {
+        let mut app = App::new("zh", false);
+        type_text(&mut app, " hello ");
+        app.scroll = 3;
+
+        assert_eq!(press(&mut app, KeyCode::Enter), Action::Submit(0));
+        assert_eq!(app.entries[0].original, "hello");
+        assert_eq!(app.entries[0].target, "zh");
+        assert!(app.input.is_empty());
+        assert_eq!(app.scroll, 0);
+    }
+
+    #[test]
+    fn blank_input_is_not_submitted() {
+        let mut app = App::new("zh", false);
+        type_text(&mut app, "  ");
+        assert_eq!(press(&mut app, KeyCode::Enter), Action::None);
+        assert!(app.entries.is_empty());
+    }
+
+    #[test]
+    fn clear_command_clears_history() {
+        let mut app = App::new("zh", false);
+        type_text(&mut app, "/clear");
+        assert_eq!(press(&mut app, KeyCode::Enter), Action::ClearHistory);
+        assert!(app.entries.is_empty());
+        assert_eq!(app.notice.as_deref(), Some("History cleared"));
+    }
+
+    #[test]
+    fn backspace_removes_the_last_character() {
+        let mut app = App::new("zh", false);
+        type_text(&mut app, "你好");
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.input, "你");
+    }
+
+    #[test]
+    fn tab_switches_the_language_of_later_entries() {
+        let mut app = App::new("zh", false);
+        type_text(&mut app, "one");
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, "two");
+        press(&mut app, KeyCode::Enter);
+
+        assert_eq!(app.entries[0].target, "zh");
+        assert_eq!(app.entries[1].target, "en");
+    }
+
+    #[test]
+    fn back_tab_wraps_around() {
+        let mut app = App::new("zh", false);
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.target(), "ru");
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.target(), "zh");
+    }
+
+    #[test]
+    fn unknown_target_is_added_first() {
+        let app = App::new("pt", false);
+        assert_eq!(app.target(), "pt");
+        assert_eq!(app.languages.len(), LANGUAGES.len() + 1);
+    }
+
+    #[test]
+    fn scrolling_stops_at_the_first_and_latest_entry() {
+        let mut app = App::new("zh", false);
+        for i in 0..12 {
+            type_text(&mut app, &format!("entry {}", i));
+            press(&mut app, KeyCode::Enter);
+        }
+        press(&mut app, KeyCode::PageUp);
+        press(&mut app, KeyCode::Up);
+        assert_eq!(app.scroll, 11);
+        press(&mut app, KeyCode::Up);
+        assert_eq!(app.scroll, 11);
+        assert_eq!(app.visible_entries().len(), 1);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.scroll, 10);
+        press(&mut app, KeyCode::PageDown);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.scroll, 0);
+    }
+
+    #[test]
+    fn panes_scroll_to_the_same_entry() {
+        let mut app = App::new("zh", false);
+        for text in ["one", "two", "three"] {
+            type_text(&mut app, text);
+            press(&mut app, KeyCode::Enter);
+        }
+        // The translation of the second entry wraps over more lines than its input
+        app.finish(0, Ok("eins".to_string()));
+        app.finish(1, Ok("zwei zwei zwei".to_string()));
+        app.finish(2, Ok("drei".to_string()));
+        press(&mut app, KeyCode::Up);
+
+        let lines: Vec<String> = translation_lines(&app, 5).iter().map(line_text).collect();
+        assert_eq!(lines, ["eins", "zwei ", "zwei ", "zwei"]);
+        let last = app.visible_entries().last().unwrap();
+        assert_eq!(last.original, "two");
+    }
+
+    #[test]
+    fn quits_on_escape_and_ctrl_d() {
+        let mut app = App::new("zh", false);
+        assert_eq!(press(&mut app, KeyCode::Esc), Action::Quit);
+        let ctrl_d = KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(app.handle_key(ctrl_d), Action::Quit);
+        assert!(app.input.is_empty());
+    }
+
+    #[test]
+    fn partial_updates_stop_once_finished() {
+        let mut app = App::new("zh", false);
+        type_text(&mut app, "hello world");
+        press(&mut app, KeyCode::Enter);
+
+        app.update(0, "你好".to_string());
+        assert_eq!(app.entries[0].partial, "你好");
+        app.finish(0, Ok("你好世界".to_string()));
+        app.update(0, "你好世".to_string());
+        assert_eq!(app.entries[0].partial, "你好");
+        assert_eq!(app.entries[0].translation, Some(Ok("你好世界".to_string())));
+    }
+
+    fn line_text(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn right_to_left_translations_are_embedded() {
+        let mut app = App::new("ar", true);
+        for text in ["one", "two"] {
+            type_text(&mut app, text);
+            press(&mut app, KeyCode::Enter);
+            press(&mut app, KeyCode::Tab);
+        }
+        app.finish(0, Ok("مرحبا".to_string()));
+        app.finish(1, Ok("你好".to_string()));
+        type_text(&mut app, "three");
+        press(&mut app, KeyCode::Enter);
+
+        let lines: Vec<String> = translation_lines(&app, 40).iter().map(line_text).collect();
+        // Only the Arabic translation, not the Chinese one or the status
+        assert_eq!(lines, ["\u{202B}مرحبا\u{202C}", "你好", "Translating..."]);
+
+        app.bidi = false;
+        assert_eq!(line_text(&translation_lines(&app, 40)[0]), "مرحبا");
+    }
+
+    #[test]
+    fn wraps_by_display_width() {
+        assert_eq!(wrap("你好世界", 4), ["你好", "世界"]);
+        assert_eq!(wrap("abc\nde", 2), ["ab", "c", "de"]);
+    }
+}
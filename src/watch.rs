@@ -0,0 +1,212 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::text;
+use crate::theme::Themed;
+use crate::translator::Translator;
+
+/// How long the file must stay quiet before a change is translated
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Re-translates a file into an output file whenever it changes
+pub struct FileWatcher<'a> {
+    translator: &'a Translator,
+    to: &'a str,
+    from: Option<&'a str>,
+    concurrency: usize,
+    /// Translations of lines seen in earlier cycles
+    cache: HashMap<String, String>,
+    last_content: Option<String>,
+}
+
+impl<'a> FileWatcher<'a> {
+    pub fn new(
+        translator: &'a Translator,
+        to: &'a str,
+        from: Option<&'a str>,
+        concurrency: usize,
+    ) -> Self {
+        Self {
+            translator,
+            to,
+            from,
+            concurrency,
+            cache: HashMap::new(),
+            last_content: None,
+        }
+    }
+
+    /// Translate `file` into `output` now and again after every change, until interrupted
+    pub async fn run(&mut self, file: &Path, output: &Path) -> Result<()> {
+        let file_name = file
+            .file_name()
+            .context("Watched path must be a file")?
+            .to_os_string();
+        let parent = match file.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        // Watch the directory so editors that save by renaming a temp file are noticed
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if let Ok(event) = event
+                    && event
+                        .paths
+                        .iter()
+                        .any(|path| path.file_name() == Some(file_name.as_os_str()))
+                {
+                    let _ = tx.send(());
+                }
+            })?;
+        watcher.watch(parent, RecursiveMode::NonRecursive)?;
+
+        println!(
+            "{} {} {} {}",
+            "Watching".status(),
+            file.display(),
+            "→".dimmed(),
+            output.display()
+        );
+        self.cycle(file, output).await;
+
+        while rx.recv().await.is_some() {
+            // Wait until the burst of events from a single save is over
+            while let Ok(Some(())) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {}
+            self.cycle(file, output).await;
+        }
+
+        Ok(())
+    }
+
+    async fn cycle(&mut self, file: &Path, output: &Path) {
+        match self.update(file, output).await {
+            Ok(Some((translated, reused))) => println!(
+                "{} {} ({} translated, {} reused)",
+                "Updated".status(),
+                output.display(),
+                translated,
+                reused
+            ),
+            Ok(None) => {}
+            Err(e) => eprintln!("{} {:#}", "Update failed:".error(), e),
+        }
+    }
+
+    /// Translate new lines and rewrite the output file.
+    /// Returns the number of translated and reused lines, or None if nothing changed.
+    async fn update(&mut self, file: &Path, output: &Path) -> Result<Option<(usize, usize)>> {
+        // The file may briefly not exist in the middle of an atomic save
+        let Ok(content) = fs::read_to_string(file) else {
+            return Ok(None);
+        };
+        let content = text::normalize_newlines(&content);
+        if self.last_content.as_ref() == Some(&content) {
+            return Ok(None);
+        }
+
+        let lines: Vec<&str> = content.lines().map(|line| line.trim()).collect();
+        let mut pending: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| !line.is_empty() && !self.cache.contains_key(*line))
+            .collect();
+        let fresh = pending.len();
+        pending.sort_unstable();
+        pending.dedup();
+
+        let results = self
+            .translator
+            .translate_batch(&pending, self.to, self.from, self.concurrency)
+            .await;
+        for (line, result) in pending.iter().zip(results) {
+            let translation = result.with_context(|| format!("Failed to translate {:?}", line))?;
+            self.cache.insert(line.to_string(), translation.text);
+        }
+
+        let translated: Vec<&str> = lines
+            .iter()
+            .map(|line| match self.cache.get(*line) {
+                Some(translation) => translation.as_str(),
+                None => "",
+            })
+            .collect();
+        fs::write(output, translated.join("\n") + "\n")
+            .with_context(|| format!("Failed to write {}", output.display()))?;
+
+        let non_blank = lines.iter().filter(|line| !line.is_empty()).count();
+        self.last_content = Some(content.clone());
+        Ok(Some((fresh, non_blank - fresh)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn translating_server(pairs: &[(&str, &str)]) -> MockServer {
+        let server = MockServer::start().await;
+        for (source, translation) in pairs {
+            Mock::given(method("POST"))
+                .and(body_string_contains(*source))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "choices": [{ "message": { "content": translation } }]
+                })))
+                .mount(&server)
+                .await;
+        }
+        server
+    }
+
+    #[tokio::test]
+    async fn only_new_lines_are_translated_again() {
+        let server = translating_server(&[
+            ("Good morning", "Guten Morgen"),
+            ("Good night", "Gute Nacht"),
+            ("Thank you", "Danke"),
+        ])
+        .await;
+        let mut config = Config::default();
+        config.set_endpoint(&server.uri());
+        let translator = Translator::new(&config);
+
+        let dir = std::env::temp_dir().join(format!("tzh-watch-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let (file, output) = (dir.join("in.txt"), dir.join("out.txt"));
+        let mut watcher = FileWatcher::new(&translator, "de", Some("en"), 1);
+
+        fs::write(&file, "Good morning\n\nGood night\nGood morning\n").unwrap();
+        let counts = watcher.update(&file, &output).await.unwrap();
+        assert_eq!(counts, Some((3, 0)));
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "Guten Morgen\n\nGute Nacht\nGuten Morgen\n"
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        // Saving the same content again changes nothing
+        assert_eq!(watcher.update(&file, &output).await.unwrap(), None);
+
+        fs::write(&file, "Good morning\r\nThank you\r\n").unwrap();
+        let counts = watcher.update(&file, &output).await.unwrap();
+        assert_eq!(counts, Some((1, 1)));
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "Guten Morgen\nDanke\n"
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,623 @@
+//! Runs the `tzh` binary against a mock chat endpoint
+
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdout, Command};
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+/// Translates by upper-casing the text of the prompt in angle brackets, failing
+/// with a server error for text that contains `FAIL`
+struct Upper;
+
+impl Respond for Upper {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let text = prompt_text(request);
+        if text.contains("FAIL") {
+            return ResponseTemplate::new(500).set_body_string("boom");
+        }
+        let content = text
+            .lines()
+            .map(|line| format!("<{}>", line.to_uppercase()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{ "message": { "content": content } }],
+            "usage": { "prompt_tokens": 10, "completion_tokens": 5 }
+        }))
+    }
+}
+
+/// Text to translate in the last user message: after the blank line of a line
+/// prompt, or after the colon of a word prompt
+fn prompt_text(request: &Request) -> String {
+    let body: Value = request.body_json().unwrap();
+    let prompt = body["messages"]
+        .as_array()
+        .and_then(|messages| messages.last())
+        .and_then(|message| message["content"].as_str())
+        .unwrap_or_default();
+    match prompt.split_once(":\n\n") {
+        Some((_, text)) => text.to_string(),
+        None => prompt.rsplit(": ").next().unwrap_or_default().to_string(),
+    }
+}
+
+/// A mock endpoint with its own config directory
+struct Tzh {
+    server: MockServer,
+    config_home: PathBuf,
+}
+
+impl Tzh {
+    /// Start an endpoint translating with `Upper`, configured by `config` on top
+    /// of the endpoint itself
+    async fn start(config: &str) -> Self {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(Upper)
+            .mount(&server)
+            .await;
+
+        let config_home = std::env::temp_dir().join(format!("tzh-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(config_home.join("tzh")).unwrap();
+        std::fs::write(
+            config_home.join("tzh").join("config.toml"),
+            format!("endpoint = \"{}\"\n{}", server.uri(), config),
+        )
+        .unwrap();
+
+        Self {
+            server,
+            config_home,
+        }
+    }
+
+    /// `tzh` with `args`, with piped standard streams
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_tzh"));
+        command
+            .arg("--no-color")
+            .args(args)
+            .env("XDG_CONFIG_HOME", &self.config_home)
+            .env("NO_COLOR", "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        command
+    }
+
+    fn spawn(&self, args: &[&str]) -> Child {
+        self.command(args).spawn().unwrap()
+    }
+
+    /// Run `tzh` with `args` and `stdin`, returning its status, stdout and stderr
+    async fn run(&self, args: &[&str], stdin: &str) -> (bool, String, String) {
+        run(self.command(args), stdin).await
+    }
+
+    async fn requests(&self) -> Vec<Request> {
+        self.server.received_requests().await.unwrap()
+    }
+
+    /// Bodies of the requests received so far
+    async fn bodies(&self) -> Vec<Value> {
+        let requests = self.requests().await;
+        requests
+            .iter()
+            .map(|request| request.body_json().unwrap())
+            .collect()
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.config_home.join("tzh").join("config.toml")
+    }
+
+    fn config_file(&self) -> String {
+        std::fs::read_to_string(self.config_path()).unwrap()
+    }
+}
+
+async fn run(mut command: Command, stdin: &str) -> (bool, String, String) {
+    let mut child = command.spawn().unwrap();
+    let mut input = child.stdin.take().unwrap();
+    input.write_all(stdin.as_bytes()).await.unwrap();
+    drop(input);
+
+    let output = child.wait_with_output().await.unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// Read from `stdout` until the output so far contains `expected`
+async fn read_until(stdout: &mut ChildStdout, output: &mut String, expected: &str) {
+    let mut buffer = [0; 1024];
+    while !output.contains(expected) {
+        let read = tokio::time::timeout(Duration::from_secs(10), stdout.read(&mut buffer))
+            .await
+            .expect("timed out waiting for output")
+            .unwrap();
+        assert!(
+            read > 0,
+            "output ended without {:?}: {:?}",
+            expected,
+            output
+        );
+        output.push_str(std::str::from_utf8(&buffer[..read]).unwrap());
+    }
+}
+
+impl Drop for Tzh {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.config_home);
+    }
+}
+
+#[tokio::test]
+async fn compact_prints_one_line_per_lookup() {
+    let tzh = Tzh::start("").await;
+    let (success, stdout, _) = tzh
+        .run(&["t", "--compact", "--to", "de"], "cat\n\nice cream\n")
+        .await;
+
+    assert!(success);
+    assert_eq!(stdout, "cat → <CAT>\nice cream → <ICE CREAM>\n");
+    assert_eq!(tzh.requests().await.len(), 2);
+}
+
+#[tokio::test]
+async fn keep_going_translates_past_a_failed_line() {
+    let tzh = Tzh::start("").await;
+    let (success, stdout, stderr) = tzh
+        .run(
+            &["t", "--stream", "--plain", "--keep-going", "--to", "de"],
+            "one\nFAIL\nthree\n",
+        )
+        .await;
+
+    assert!(!success);
+    assert_eq!(stdout, "<ONE>\n[ERROR]\n<THREE>\n");
+    assert!(stderr.starts_with("Line 2 failed: "), "{}", stderr);
+    assert!(
+        stderr.ends_with("1 line(s) failed to translate\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[tokio::test]
+async fn failed_line_stops_without_keep_going() {
+    let tzh = Tzh::start("").await;
+    let (success, stdout, stderr) = tzh
+        .run(&["t", "--stream", "--plain", "--to", "de"], "FAIL\nthree\n")
+        .await;
+
+    assert!(!success);
+    assert_eq!(stdout, "");
+    assert!(stderr.starts_with("Translation failed: "), "{}", stderr);
+    // Only the retries of the first line were sent
+    assert_eq!(tzh.requests().await.len(), 3);
+}
+
+#[tokio::test]
+async fn output_keeps_the_line_ending_of_the_input() {
+    let tzh = Tzh::start("").await;
+    let (_, stdout, _) = tzh
+        .run(
+            &["t", "--stream", "--plain", "--to", "de"],
+            "one\r\ntwo\r\n",
+        )
+        .await;
+    assert_eq!(stdout, "<ONE>\r\n<TWO>\r\n");
+
+    let (_, stdout, _) = tzh
+        .run(
+            &[
+                "t",
+                "--stream",
+                "--plain",
+                "--line-ending",
+                "lf",
+                "--to",
+                "de",
+            ],
+            "one\r\ntwo\r\n",
+        )
+        .await;
+    assert_eq!(stdout, "<ONE>\n<TWO>\n");
+}
+
+#[tokio::test]
+async fn max_tokens_is_overridden_for_one_run() {
+    let tzh = Tzh::start("max_tokens = 2000").await;
+    tzh.run(&["t", "--max-tokens", "50", "--to", "de"], "Hello")
+        .await;
+    tzh.run(&["t", "--unlimited", "--to", "de"], "Hello").await;
+    tzh.run(&["t", "--to", "de"], "Hello").await;
+
+    let bodies = tzh.bodies().await;
+    assert_eq!(bodies[0]["max_tokens"], 50);
+    assert!(bodies[1]["max_tokens"].is_null());
+    assert_eq!(bodies[2]["max_tokens"], 2000);
+    assert!(tzh.config_file().contains("max_tokens = 2000"));
+}
+
+#[tokio::test]
+async fn consecutive_failures_abort_the_run() {
+    let tzh = Tzh::start("").await;
+    let (success, stdout, stderr) = tzh
+        .run(
+            &[
+                "t",
+                "--stream",
+                "--plain",
+                "--keep-going",
+                "--max-line-failures",
+                "2",
+                "--to",
+                "de",
+            ],
+            "one\nFAIL\nFAIL again\nfour\n",
+        )
+        .await;
+
+    assert!(!success);
+    // The run stops before a placeholder for the last failure is printed
+    assert_eq!(stdout, "<ONE>\n[ERROR]\n");
+    assert!(
+        stderr.ends_with("2 consecutive lines failed, aborting after 1 completed line(s)\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[tokio::test]
+async fn a_success_resets_the_failure_count() {
+    let tzh = Tzh::start("").await;
+    let (success, stdout, _) = tzh
+        .run(
+            &[
+                "t",
+                "--stream",
+                "--plain",
+                "--keep-going",
+                "--max-line-failures",
+                "2",
+                "--to",
+                "de",
+            ],
+            "FAIL\ntwo\nFAIL\nfour\n",
+        )
+        .await;
+
+    assert!(!success);
+    assert_eq!(stdout, "[ERROR]\n<TWO>\n[ERROR]\n<FOUR>\n");
+}
+
+#[tokio::test]
+async fn interactive_session_picks_up_config_changes() {
+    let tzh = Tzh::start("model = \"first-model\"").await;
+    let mut child = tzh.spawn(&["i", "--watch-config", "--no-summary", "--to", "de"]);
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let mut output = String::new();
+
+    stdin.write_all(b"Hello\n").await.unwrap();
+    read_until(&mut stdout, &mut output, "<HELLO>").await;
+
+    let config = tzh.config_file().replace("first-model", "second-model");
+    std::fs::write(tzh.config_path(), config).unwrap();
+    stdin.write_all(b"Bye\n").await.unwrap();
+    read_until(&mut stdout, &mut output, "<BYE>").await;
+    drop(stdin);
+    assert!(child.wait().await.unwrap().success());
+
+    assert!(output.contains("Config reloaded"), "{}", output);
+    let bodies = tzh.bodies().await;
+    assert_eq!(bodies[0]["model"], "first-model");
+    assert_eq!(bodies[1]["model"], "second-model");
+}
+
+#[tokio::test]
+async fn stream_mode_keeps_blank_lines() {
+    let tzh = Tzh::start("").await;
+    let input = "\none two\n\n\nthree four\n";
+    let (_, stdout, _) = tzh
+        .run(&["t", "--stream", "--plain", "--to", "de"], input)
+        .await;
+    assert_eq!(stdout, "\n<ONE TWO>\n\n\n<THREE FOUR>\n");
+
+    let (_, stdout, _) = tzh
+        .run(
+            &[
+                "t",
+                "--stream",
+                "--plain",
+                "--collapse-blanks",
+                "--to",
+                "de",
+            ],
+            input,
+        )
+        .await;
+    assert_eq!(stdout, "\n<ONE TWO>\n\n<THREE FOUR>\n");
+    assert_eq!(tzh.requests().await.len(), 4);
+}
+
+#[tokio::test]
+async fn closed_output_stops_the_translation() {
+    let tzh = Tzh::start("").await;
+    Mock::given(method("POST"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "choices": [{ "message": { "content": "x" } }] }))
+                .set_delay(Duration::from_millis(20)),
+        )
+        .with_priority(1)
+        .mount(&tzh.server)
+        .await;
+
+    let mut child = tzh.spawn(&["t", "--stream", "--plain", "--to", "de"]);
+    let mut stdin = child.stdin.take().unwrap();
+    stdin
+        .write_all("a line to translate\n".repeat(100).as_bytes())
+        .await
+        .unwrap();
+    drop(stdin);
+    let mut stdout = child.stdout.take().unwrap();
+    read_until(&mut stdout, &mut String::new(), "x\n").await;
+    drop(stdout);
+
+    // Exits as if killed by SIGPIPE, as shells report it
+    let status = child.wait().await.unwrap();
+    assert_eq!(status.code(), Some(141));
+    assert!(tzh.requests().await.len() < 100);
+}
+
+#[tokio::test]
+async fn piped_right_to_left_output_is_left_as_it_is() {
+    let tzh = Tzh::start("").await;
+    for args in [
+        &["t", "--plain", "--to", "ar"][..],
+        &["t", "--stream", "--plain", "--to", "he"],
+        &["i", "--no-summary", "--to", "ar"],
+    ] {
+        let (_, stdout, _) = tzh.run(args, "Hello\n").await;
+        assert!(stdout.contains("<HELLO>"), "{}", stdout);
+        assert!(!stdout.contains(['\u{202B}', '\u{202C}']), "{:?}", stdout);
+    }
+}
+
+#[tokio::test]
+async fn echo_config_prints_the_settings_in_use() {
+    let tzh = Tzh::start("api_key = \"sk-secret\"\nmodel = \"echo-model\"").await;
+    let (_, stdout, stderr) = tzh
+        .run(
+            &[
+                "t",
+                "--plain",
+                "--echo-config",
+                "--max-tokens",
+                "50",
+                "--to",
+                "de",
+            ],
+            "Hello",
+        )
+        .await;
+
+    assert_eq!(stdout, "<HELLO>\n");
+    let line = stderr
+        .lines()
+        .find(|line| line.starts_with("tzh-config: "))
+        .unwrap();
+    assert!(line.contains(" model=echo-model "), "{}", line);
+    assert!(line.contains(" max_tokens=50 "), "{}", line);
+    assert!(line.contains(" api_key=<redacted> "), "{}", line);
+    assert!(
+        line.ends_with(" auto_tokens=false to=de from=auto"),
+        "{}",
+        line
+    );
+    assert!(!stderr.contains("sk-secret"));
+}
+
+#[tokio::test]
+async fn output_to_a_pipe_is_never_paged() {
+    let tzh = Tzh::start("pager = true").await;
+    let paged = tzh.config_home.join("paged");
+    let mut command = tzh.command(&["t", "--pager", "--to", "de"]);
+    command.env("PAGER", format!("cat > {}", paged.display()));
+
+    let (success, stdout, _) = run(command, "Hello").await;
+    assert!(success);
+    assert!(stdout.ends_with("<HELLO>\n"), "{}", stdout);
+    assert!(!paged.exists());
+}
+
+#[tokio::test]
+async fn reasoning_is_shown_under_the_translation() {
+    let tzh = Tzh::start("").await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{ "message": {
+                "content": "Hallo Welt",
+                "reasoning_content": "  A common greeting.  "
+            } }],
+            "usage": {
+                "prompt_tokens": 10,
+                "completion_tokens": 50,
+                "completion_tokens_details": { "reasoning_tokens": 42 }
+            }
+        })))
+        .with_priority(1)
+        .mount(&tzh.server)
+        .await;
+
+    let (_, stdout, _) = tzh
+        .run(&["t", "--show-reasoning", "--to", "de"], "Hello world")
+        .await;
+    assert!(
+        stdout.ends_with("Hallo Welt\nReasoning (42 tokens):\nA common greeting.\n"),
+        "{}",
+        stdout
+    );
+
+    // Without the flag the reasoning stays hidden
+    let (_, stdout, _) = tzh.run(&["t", "--to", "de"], "Hello world").await;
+    assert!(!stdout.contains("Reasoning"), "{}", stdout);
+}
+
+#[tokio::test]
+async fn only_selected_fields_are_translated() {
+    let tzh = Tzh::start("").await;
+    let (success, stdout, _) = tzh
+        .run(
+            &[
+                "t",
+                "--input-separator",
+                "tab",
+                "--translate-fields",
+                "2",
+                "--to",
+                "de",
+            ],
+            "key.hello\tHello\tkeep\nkey.bye\tGood bye\tkeep\n",
+        )
+        .await;
+
+    assert!(success);
+    assert_eq!(
+        stdout,
+        "key.hello\t<HELLO>\tkeep\nkey.bye\t<GOOD BYE>\tkeep\n"
+    );
+    assert_eq!(tzh.requests().await.len(), 2);
+}
+
+#[tokio::test]
+async fn complete_config_file_is_left_as_it_is() {
+    let tzh = Tzh::start("").await;
+    // The first run adds the missing fields
+    tzh.run(&["t", "--plain", "--to", "de"], "Hello").await;
+    assert!(tzh.config_file().contains("model = "));
+
+    let edited = format!("# My settings\n{}", tzh.config_file());
+    std::fs::write(tzh.config_path(), &edited).unwrap();
+    let (success, _, _) = tzh.run(&["t", "--plain", "--to", "de"], "Hello").await;
+    assert!(success);
+    assert_eq!(tzh.config_file(), edited);
+}
+
+#[tokio::test]
+async fn final_newline_can_be_left_out_in_every_mode() {
+    let tzh = Tzh::start("").await;
+    let modes: [(&[&str], &str, &str); 5] = [
+        (&["--plain"], "Hello world\n", "<HELLO WORLD>"),
+        (&["--stream", "--plain"], "one\ntwo\n", "<ONE>\n<TWO>"),
+        (&["--stream", "--plain"], "one\r\ntwo\r\n", "<ONE>\r\n<TWO>"),
+        (
+            &["--compact"],
+            "cat\nice cream\n",
+            "cat → <CAT>\nice cream → <ICE CREAM>",
+        ),
+        // Passed through with its own line endings
+        (
+            &["--input-separator", "tab", "--translate-fields", "2"],
+            "a\tone\nb\ttwo\n",
+            "a\t<ONE>\nb\t<TWO>",
+        ),
+    ];
+
+    for (flags, input, expected) in modes {
+        let args = [&["t", "--to", "de"], flags].concat();
+        let (success, stdout, stderr) = tzh.run(&args, input).await;
+        assert!(success, "{}", stderr);
+        assert_eq!(stdout, format!("{}{}", expected, line_ending(input)));
+
+        let args = [&args[..], &["--no-final-newline"]].concat();
+        let (success, stdout, stderr) = tzh.run(&args, input).await;
+        assert!(success, "{}", stderr);
+        assert_eq!(stdout, expected);
+    }
+}
+
+fn line_ending(text: &str) -> &'static str {
+    if text.contains("\r\n") { "\r\n" } else { "\n" }
+}
+
+#[tokio::test]
+async fn failed_lines_are_retried_from_the_error_report() {
+    let tzh = Tzh::start("").await;
+    let report = tzh.config_home.join("errors.csv");
+    let report = report.to_str().unwrap();
+    let (success, stdout, _) = tzh
+        .run(
+            &[
+                "t",
+                "--stream",
+                "--plain",
+                "--keep-going",
+                "--error-report",
+                report,
+                "--to",
+                "de",
+            ],
+            "one\nFAIL again\nthree\n",
+        )
+        .await;
+    assert!(!success);
+    assert_eq!(stdout, "<ONE>\n[ERROR]\n<THREE>\n");
+    let csv = std::fs::read_to_string(report).unwrap();
+    assert!(
+        csv.starts_with("line,field,source,error\n2,,FAIL again,"),
+        "{}",
+        csv
+    );
+
+    // The endpoint has recovered
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{ "message": { "content": "<FIXED>" } }]
+        })))
+        .with_priority(1)
+        .mount(&tzh.server)
+        .await;
+    let (success, stdout, stderr) = tzh
+        .run(&["t", "--retry-errors", report, "--to", "de"], &stdout)
+        .await;
+    assert!(success, "{}", stderr);
+    assert_eq!(stdout, "<ONE>\n<FIXED>\n<THREE>\n");
+}
+
+#[tokio::test]
+async fn interactive_session_ends_with_a_summary() {
+    let tzh = Tzh::start("[pricing]\ninput = 1000.0\noutput = 2000.0").await;
+    let (success, stdout, _) = tzh.run(&["i", "--to", "de"], "Hello\nFAIL\nBye\n").await;
+
+    assert!(success);
+    let summary = &stdout[stdout.find("Session summary:").expect(&stdout)..];
+    let lines: Vec<&str> = summary.lines().collect();
+    assert_eq!(
+        lines[..5],
+        [
+            "Session summary:",
+            "  Translations: 2 (1 failed)",
+            "  Characters: 8",
+            "  Tokens: 20 input, 10 output",
+            "  Estimated cost: 0.0400",
+        ]
+    );
+    assert!(lines[5].starts_with("  Average latency: "), "{}", summary);
+
+    let (_, stdout, _) = tzh
+        .run(&["i", "--no-summary", "--to", "de"], "Hello\n")
+        .await;
+    assert!(!stdout.contains("Session summary:"), "{}", stdout);
+}
@@ -0,0 +1,42 @@
+//! Uses the translator through the public `tzh` crate
+
+use serde_json::json;
+use tzh::config::Config;
+use tzh::translator::Translator;
+use wiremock::matchers::{body_string_contains, method};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn reply(content: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "choices": [{ "message": { "content": content } }]
+    }))
+}
+
+#[tokio::test]
+async fn translates_a_batch_through_the_library() {
+    let server = MockServer::start().await;
+    for (text, translation) in [("one", "eins"), ("two", "zwei")] {
+        Mock::given(method("POST"))
+            .and(body_string_contains(text))
+            .respond_with(reply(translation))
+            .mount(&server)
+            .await;
+    }
+    Mock::given(method("POST"))
+        .and(body_string_contains("broken"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("bad request"))
+        .mount(&server)
+        .await;
+
+    let mut config = Config::default();
+    config.set_endpoint(&server.uri());
+    let translator = Translator::with_client(&config, reqwest::Client::new());
+
+    let results = translator
+        .translate_batch(&["one", "broken", "two"], "de", Some("en"), 2)
+        .await;
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().text, "eins");
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_ref().unwrap().text, "zwei");
+}